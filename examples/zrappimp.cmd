@@ -5,4 +5,6 @@ set EASYCASHIER_PASSWORD=
 REM set EASYCASHIER_COMPANY=
 set BOKIO_API_TOKEN=
 set BOKIO_COMPANY_ID=
+REM set ECBOKIO_LEDGER=fortnox
+REM set FORTNOX_ACCESS_TOKEN=
 ecbokio %*
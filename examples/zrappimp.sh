@@ -8,6 +8,8 @@ export EASYCASHIER_PASSWORD=""
 #export EASYCASHIER_COMPANY=""
 export BOKIO_API_TOKEN=""
 export BOKIO_COMPANY_ID=""
+#export ECBOKIO_LEDGER="fortnox"
+#export FORTNOX_ACCESS_TOKEN=""
 
 ecbokio=ecbokio
 for d in "$SCRIPT_DIR" "$SCRIPT_DIR/../target/debug" "$SCRIPT_DIR/../target/release"; do
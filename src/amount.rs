@@ -0,0 +1,89 @@
+//! Serialisering av belopp som exakta decimaltal med två decimaler.
+//!
+//! Används med `#[serde(with = "crate::amount")]` i stället för
+//! `rust_decimal::serde::float` så att belopp inte räknas om via `f64`.
+//! JSON-talet tolkas från exakt den text som skickades. Belopp med fler
+//! decimaler än öre ger ett fel. Momssatser och andra tal som inte är
+//! belopp hanteras av [`rate`].
+
+use rust_decimal::Decimal;
+use serde::de::{Error as DeError, Unexpected};
+use serde::ser::Error as SerError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use serde_json::value::RawValue;
+use std::str::FromStr;
+
+/// Antal decimaler i belopp (öre).
+pub const SCALE: u32 = 2;
+
+fn check_scale(value: Decimal) -> Result<Decimal, String> {
+    if value.normalize().scale() > SCALE {
+        Err(format!("beloppet {} har fler decimaler än öre", value))
+    } else {
+        Ok(value)
+    }
+}
+
+fn parse(text: &str) -> Result<Decimal, String> {
+    let value = Decimal::from_str(text)
+        .or_else(|_| Decimal::from_scientific(text))
+        .map_err(|e| format!("ogiltigt belopp {}: {}", text, e))?;
+    check_scale(value)
+}
+
+/// Talets text som det stod i JSON. Med `arbitrary_precision` behåller
+/// serde_json talets text, så inget räknas om via `f64`.
+fn text<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(n) => Ok(n.as_str().to_string()),
+        Value::String(s) => Ok(s),
+        v => Err(D::Error::invalid_type(Unexpected::Other(&v.to_string()), &"ett tal")),
+    }
+}
+
+pub fn serialize<S: Serializer>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut value = check_scale(*value).map_err(S::Error::custom)?;
+    value.rescale(SCALE);
+    let raw = RawValue::from_string(value.to_string()).map_err(S::Error::custom)?;
+    raw.serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Decimal, D::Error> {
+    parse(&text(deserializer)?).map_err(D::Error::custom)
+}
+
+/// Exakta decimaltal som inte är belopp, t.ex. momssatser. Talet tolkas
+/// från JSON-texten utan krav på antal decimaler och skrivs tillbaka
+/// oförändrat.
+pub mod rate {
+    use rust_decimal::Decimal;
+    use serde::de::Error as DeError;
+    use serde::ser::Error as SerError;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::value::RawValue;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(value: &Option<Decimal>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => {
+                let raw = RawValue::from_string(value.to_string()).map_err(S::Error::custom)?;
+                raw.serialize(serializer)
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Decimal>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapper(#[serde(deserialize_with = "super::text")] String);
+
+        let Some(Wrapper(text)) = Option::<Wrapper>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        Decimal::from_str(&text)
+            .or_else(|_| Decimal::from_scientific(&text))
+            .map(Some)
+            .map_err(|e| D::Error::custom(format!("ogiltigt tal {}: {}", text, e)))
+    }
+}
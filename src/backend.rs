@@ -0,0 +1,61 @@
+//! Gränssnitt mot kassasystem och bokföringssystem.
+//!
+//! Importen använder bara dessa traits så att andra kassasystem och
+//! bokföringssystem kan läggas till bredvid EasyCashier och Bokio, och så
+//! att tester kan använda implementationer i minnet. Gränssnitten använder
+//! bara typerna i `ledger` och `zrapport` och felen i `error`, inte något
+//! enskilt systems klient.
+
+use crate::error::Error;
+use crate::ledger::{Account, CreateJournal, FiscalYear, JournalEntry};
+use crate::zrapport::{DateRequest, ZRapport, ZRapportId};
+use chrono::NaiveDate;
+
+/// Kassasystem som Z-Rapporter hämtas från.
+pub trait ZReportSource {
+    /// Organisationsnummer för företaget vars rapporter hämtas.
+    fn company(&self) -> &str;
+
+    /// Alla Z-Rapporter inom datumintervallet, sorterade på Z-nummer.
+    fn list_reports(&self, date_req: &DateRequest) -> Result<Vec<ZRapport>, Error>;
+
+    /// En enskild Z-Rapport.
+    fn report(&self, id: &ZRapportId) -> Result<ZRapport, Error>;
+
+    /// Z-Rapporten som PDF och filnamn att spara den under.
+    fn document(&self, rapport: &ZRapport) -> Result<(Vec<u8>, String), Error>;
+}
+
+/// Bokföringssystem som verifikat skapas i.
+pub trait LedgerSink {
+    /// Namn på systemet i meddelanden, t.ex. `Bokio`.
+    fn name(&self) -> &str;
+
+    /// Organisationsnummer för företaget i bokföringssystemet.
+    fn organization_number(&self) -> Result<String, Error>;
+
+    fn fiscal_years(&self) -> Result<Vec<FiscalYear>, Error>;
+
+    fn chart_of_accounts(&self) -> Result<Vec<Account>, Error>;
+
+    /// Verifikat mellan `start_date` och `end_date` vars namn börjar med
+    /// `title_prefix`. Raderna kan saknas om systemet inte listar dem, hämta
+    /// dem då med `entry`.
+    fn list_entries(
+        &self,
+        start_date: Option<NaiveDate>,
+        end_date: Option<NaiveDate>,
+        title_prefix: Option<&str>,
+    ) -> Result<Vec<JournalEntry>, Error>;
+
+    /// Verifikatet med rader. Standard är verifikatet från listningen, för
+    /// system vars listning redan innehåller raderna.
+    fn entry(&self, entry: &JournalEntry) -> Result<JournalEntry, Error> {
+        Ok(entry.clone())
+    }
+
+    fn create_entry(&self, entry: &CreateJournal) -> Result<JournalEntry, Error>;
+
+    /// Bifoga en fil som underlag till ett verifikat.
+    fn attach_file(&self, filename: &str, content_type: &str, entry: &JournalEntry) -> Result<(), Error>;
+}
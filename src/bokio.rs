@@ -1,42 +1,21 @@
+use crate::backend::LedgerSink;
+use crate::error::Error;
+use crate::ledger::{Account, CreateJournal, FiscalYear, JournalEntry};
 use crate::utils::{APPLICATION_JSON, PageReq};
 use chrono::NaiveDate;
 use http::header::{ACCEPT, AUTHORIZATION};
 use mime::Mime;
 use multipart::client::lazy::Multipart;
-use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::str::FromStr;
-use ureq::Error;
+use ureq::Agent;
 
 pub const BOKIO_API_URL: &str = "https://api.bokio.se";
 
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
-pub struct JournalEntryAccount {
-    pub id: i64,
-    pub account: i32,
-    #[serde(with = "rust_decimal::serde::float")]
-    pub debit: Decimal,
-    #[serde(with = "rust_decimal::serde::float")]
-    pub credit: Decimal,
-}
-
 #[derive(Debug, Clone, Deserialize)]
 #[allow(unused)]
-pub struct JournalEntry {
-    pub id: String,
-    pub title: String,
-    #[serde(rename = "journalEntryNumber")]
-    pub journal_entry_number: String,
-    pub date: String,
-    pub items: Vec<JournalEntryAccount>,
-    #[serde(rename = "reversingJournalEntryId")]
-    pub reversing_journal_entry_id: Option<String>,
-    #[serde(rename = "reversedByJournalEntryId")]
-    pub reversed_by_journal_entry_id: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-#[allow(unused)]
 pub struct JournalEntryListing {
     #[serde(rename = "totalItems")]
     pub total_items: u32,
@@ -44,23 +23,26 @@ pub struct JournalEntryListing {
     pub total_pages: u32,
     #[serde(rename = "currentPage")]
     pub current_page: u32,
-    pub items: Vec<JournalEntry>,
-}
-
-#[derive(Serialize)]
-pub struct CreateJournalAccount {
-    pub account: i32,
-    #[serde(with = "rust_decimal::serde::float")]
-    pub debit: Decimal,
-    #[serde(with = "rust_decimal::serde::float")]
-    pub credit: Decimal,
+    /// Verifikaten tolkas var för sig så att felet kan visa vilket
+    /// verifikat som inte kunde tolkas, se [`JournalEntryListing::entries`].
+    pub items: Vec<serde_json::Value>,
 }
 
-#[derive(Serialize)]
-pub struct CreateJournal {
-    pub title: String,
-    pub date: String,
-    pub items: Vec<CreateJournalAccount>,
+impl JournalEntryListing {
+    /// Tolka verifikaten i listningen. Om något verifikat inte kan tolkas
+    /// misslyckas hela listningen, annars skulle ett redan bokfört
+    /// verifikat kunna missas och bokföras igen.
+    pub fn entries(&self) -> Result<Vec<JournalEntry>, Error> {
+        self.items
+            .iter()
+            .map(|item| {
+                serde_json::from_value::<JournalEntry>(item.clone()).map_err(|e| {
+                    let id = item.get("id").and_then(|id| id.as_str()).unwrap_or("?");
+                    Error::Other(format!("Bokio: verifikat {}: {}", id, e))
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -74,33 +56,117 @@ pub struct UploadResponse {
     pub journal_entry_id: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct Company {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "organizationNumber")]
+    pub organization_number: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(unused)]
+pub struct FiscalYearListing {
+    #[serde(rename = "totalItems")]
+    pub total_items: u32,
+    #[serde(rename = "totalPages")]
+    pub total_pages: u32,
+    #[serde(rename = "currentPage")]
+    pub current_page: u32,
+    pub items: Vec<FiscalYear>,
+}
+
 pub struct Bokio {
+    /// Agent för alla anrop, kan ersättas för inspelning och uppspelning.
+    pub agent: Agent,
     base_url: String,
     company_id: String,
     auth_header: String,
+    /// Antal verifikat per sida vid listning.
+    pub page_size: u32,
+    /// Hämtade sidor med verifikat under körningen, nyckel är URL och filter.
+    journal_cache: RefCell<HashMap<String, JournalEntryListing>>,
 }
 
 impl Bokio {
     pub fn new(base_url: &str, company_id: &str, token: &str) -> Self {
         Self {
+            agent: Agent::new_with_defaults(),
             base_url: base_url.to_string(),
             company_id: company_id.to_string(),
             auth_header: format!("Bearer {}", token),
+            page_size: 100,
+            journal_cache: RefCell::new(HashMap::new()),
         }
     }
 
+    pub fn company(&self) -> Result<Company, Error> {
+        let url = format!("{}/companies/{}", self.base_url, self.company_id);
+
+        self.agent.get(url)
+            .header(ACCEPT, APPLICATION_JSON)
+            .header(AUTHORIZATION, &self.auth_header)
+            .call()?
+            .body_mut()
+            .read_json::<Company>()
+            .map_err(Error::from)
+    }
+
+    pub fn fiscal_years(&self) -> Result<Vec<FiscalYear>, Error> {
+        let mut page = PageReq { page: 1, size: 100 };
+        let mut result: Vec<FiscalYear> = Vec::new();
+        loop {
+            let url = format!(
+                "{}/companies/{}/fiscal-years?page={}&pageSize={}",
+                self.base_url, self.company_id, page.page, page.size
+            );
+            let lst = self.agent.get(url)
+                .header(ACCEPT, APPLICATION_JSON)
+                .header(AUTHORIZATION, &self.auth_header)
+                .call()?
+                .body_mut()
+                .read_json::<FiscalYearListing>()?;
+            let empty = lst.items.is_empty();
+            result.extend(lst.items);
+            if empty || page.page >= lst.total_pages {
+                break;
+            }
+            page.page += 1;
+        }
+
+        Ok(result)
+    }
+
+    pub fn chart_of_accounts(&self) -> Result<Vec<Account>, Error> {
+        let url = format!(
+            "{}/companies/{}/chart-of-accounts",
+            self.base_url, self.company_id
+        );
+
+        self.agent.get(url)
+            .header(ACCEPT, APPLICATION_JSON)
+            .header(AUTHORIZATION, &self.auth_header)
+            .call()?
+            .body_mut()
+            .read_json::<Vec<Account>>()
+            .map_err(Error::from)
+    }
+
     pub fn create_journal_entry(&self, entry: &CreateJournal) -> Result<JournalEntry, Error> {
         let url = format!(
             "{}/companies/{}/journal-entries",
             self.base_url, self.company_id
         );
 
-        ureq::post(&url)
+        self.journal_cache.borrow_mut().clear();
+        self.agent.post(&url)
             .header(ACCEPT, APPLICATION_JSON)
             .header(AUTHORIZATION, &self.auth_header)
-            .send_json(&entry)?
+            .send_json(entry)?
             .body_mut()
             .read_json::<JournalEntry>()
+            .map_err(Error::from)
     }
 
     pub fn upload(
@@ -112,12 +178,12 @@ impl Bokio {
         let url = format!("{}/companies/{}/uploads", self.base_url, self.company_id);
 
         let mut m = Multipart::new();
-        let file = std::fs::File::open(&filename).expect(&format!("Kunde inte öppna {}", filename));
-        let basename = std::path::Path::new(&filename)
+        let file = std::fs::File::open(filename)
+            .map_err(|err| Error::Other(format!("Kunde inte öppna {}: {}", filename, err)))?;
+        let basename = std::path::Path::new(filename)
             .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::Other(format!("Ogiltigt filnamn {}", filename)))?
             .to_string();
         m.add_stream(
             "file",
@@ -127,87 +193,131 @@ impl Bokio {
         );
         m.add_text("journalEntryId", journal_entry_id);
 
-        let mut prepared = m.prepare().unwrap();
+        let mut prepared = m
+            .prepare()
+            .map_err(|err| Error::Other(format!("Kunde inte läsa {}: {}", filename, err.error)))?;
         let mut vec: Vec<u8> = Vec::new();
-        std::io::copy(&mut prepared, &mut vec).unwrap();
+        std::io::copy(&mut prepared, &mut vec)
+            .map_err(|err| Error::Other(format!("Kunde inte läsa {}: {}", filename, err)))?;
         let boundary = prepared.boundary();
-        ureq::post(url)
+        self.agent.post(url)
             .content_type(format!("multipart/form-data; boundary={}", boundary))
             .header(ACCEPT, APPLICATION_JSON)
             .header(AUTHORIZATION, &self.auth_header)
             .send(&vec)?
             .body_mut()
             .read_json::<UploadResponse>()
+            .map_err(Error::from)
     }
 
-    fn _list_journal_entries(&self, page: &PageReq) -> Result<JournalEntryListing, Error> {
+    fn _list_journal_entries(&self, page: &PageReq, query: &str) -> Result<JournalEntryListing, Error> {
         let url = format!(
             "{}/companies/{}/journal-entries?page={}&pageSize={}",
             self.base_url, self.company_id, page.page, page.size
         );
 
-        ureq::get(url)
+        let key = format!("{}&query={}", url, query);
+        if let Some(lst) = self.journal_cache.borrow().get(&key) {
+            return Ok(lst.clone());
+        }
+
+        let mut req = self.agent.get(url);
+        if !query.is_empty() {
+            req = req.query("query", query);
+        }
+        let lst = req
             .header(ACCEPT, APPLICATION_JSON)
             .header(AUTHORIZATION, &self.auth_header)
             .call()?
             .body_mut()
-            .read_json::<JournalEntryListing>()
+            .read_json::<JournalEntryListing>()?;
+        self.journal_cache.borrow_mut().insert(key, lst.clone());
+        Ok(lst)
     }
 
+    /// Hämta verifikat mellan `start_date` och `end_date` vars namn börjar med `title_prefix`.
+    ///
+    /// Filtret skickas till Bokio som `query` men kontrolleras även här,
+    /// sidorna hämtas tills det första verifikatet före `start_date`.
     pub fn list_journal(
         &self,
         start_date: Option<NaiveDate>,
         end_date: Option<NaiveDate>,
+        title_prefix: Option<&str>,
     ) -> Result<Vec<JournalEntry>, Error> {
-        let mut page = PageReq { page: 1, size: 100 };
+        let mut filter: Vec<String> = Vec::new();
+        if let Some(start_date) = start_date {
+            filter.push(format!("date>={}", start_date.format("%Y-%m-%d")));
+        }
+        if let Some(end_date) = end_date {
+            filter.push(format!("date<={}", end_date.format("%Y-%m-%d")));
+        }
+        if let Some(prefix) = title_prefix.filter(|p| !p.is_empty()) {
+            filter.push(format!("title~'{}*'", prefix.replace('\'', "\\'")));
+        }
+        let query = filter.join("&&");
+
+        let mut page = PageReq { page: 1, size: self.page_size };
         let mut result: Vec<JournalEntry> = Vec::new();
-        let mut reached_end = false;
         // Results are returned in descending order
-        while !reached_end {
-            let lst = self._list_journal_entries(&page)?;
-            if lst.items.is_empty() {
-                break;
-            }
-
-            if let Some(start_date) = start_date {
-                if let Some(end_date) = end_date {
-                    for entry in lst.items {
-                        let date = entry.date.parse::<NaiveDate>().unwrap();
-                        if date < start_date {
-                            reached_end = true;
-                            break;
-                        } else if date <= end_date && entry.title.starts_with("Z") {
-                            result.push(entry);
-                        }
-                    }
-                } else {
-                    for entry in lst.items {
-                        let date = entry.date.parse::<NaiveDate>().unwrap();
-                        if date < start_date {
-                            reached_end = true;
-                        } else if entry.title.starts_with("Z") {
-                            result.push(entry);
-                        }
-                    }
+        loop {
+            let lst = self._list_journal_entries(&page, &query)?;
+            let mut reached_end = lst.items.is_empty() || page.page >= lst.total_pages;
+            for entry in lst.entries()? {
+                let date = entry.date;
+                if start_date.is_some_and(|start_date| date < start_date) {
+                    reached_end = true;
+                    break;
                 }
-            } else if let Some(end_date) = end_date {
-                for entry in lst.items {
-                    let date = entry.date.parse::<NaiveDate>().unwrap();
-                    if date <= end_date && entry.title.starts_with("Z") {
-                        result.push(entry);
-                    }
-                }
-            } else {
-                for entry in lst.items {
-                    if entry.title.starts_with("Z") {
-                        result.push(entry);
-                    }
+
+                let in_range = end_date.is_none_or(|end_date| date <= end_date);
+                let title_match = title_prefix.is_none_or(|p| entry.title.starts_with(p));
+                if in_range && title_match {
+                    result.push(entry);
                 }
             }
 
+            if reached_end {
+                break;
+            }
             page.page += 1;
         }
 
         Ok(result)
     }
 }
+
+impl LedgerSink for Bokio {
+    fn name(&self) -> &str {
+        "Bokio"
+    }
+
+    fn organization_number(&self) -> Result<String, Error> {
+        self.company().map(|c| c.organization_number)
+    }
+
+    fn fiscal_years(&self) -> Result<Vec<FiscalYear>, Error> {
+        Bokio::fiscal_years(self)
+    }
+
+    fn chart_of_accounts(&self) -> Result<Vec<Account>, Error> {
+        Bokio::chart_of_accounts(self)
+    }
+
+    fn list_entries(
+        &self,
+        start_date: Option<NaiveDate>,
+        end_date: Option<NaiveDate>,
+        title_prefix: Option<&str>,
+    ) -> Result<Vec<JournalEntry>, Error> {
+        self.list_journal(start_date, end_date, title_prefix)
+    }
+
+    fn create_entry(&self, entry: &CreateJournal) -> Result<JournalEntry, Error> {
+        self.create_journal_entry(entry)
+    }
+
+    fn attach_file(&self, filename: &str, content_type: &str, entry: &JournalEntry) -> Result<(), Error> {
+        self.upload(filename, content_type, &entry.id).map(|_| ())
+    }
+}
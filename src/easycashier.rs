@@ -1,17 +1,18 @@
-use std::collections::HashMap;
-use serde::{Deserialize, Serialize};
-use ureq::Error;
+use serde::Deserialize;
+use ureq::Agent;
 use http::header::{ACCEPT, USER_AGENT};
 use serde_json::Value;
-use chrono::NaiveDate;
-use rust_decimal::Decimal;
+use crate::backend::ZReportSource;
+use crate::error::Error;
 use crate::utils;
 use crate::utils::{PageReq, APPLICATION_JSON, DEFAULT_USER_AGENT};
+use crate::zrapport::{DateRequest, ZRapport, ZRapportId};
 
 pub const EASYCASHIER_URL: &str = "https://backoffice.easycashier.se";
 
 #[derive(Debug)]
 pub struct EasyCashier {
+    agent: Agent,
     base_url: String,
     pub company: String,
     token: String,
@@ -28,76 +29,25 @@ pub struct EasyCashierMetaInformation {
     pub total_resources: u32,
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct ZRapportTrans {
-    #[serde(rename = "accountNumber")]
-    pub account_number: u16,
-    #[serde(with = "rust_decimal::serde::float")]
-    pub amount: Decimal,
-    #[serde(flatten)]
-    pub other: HashMap<String, Value>,
-}
-
-#[derive(Deserialize, Serialize)]
-pub struct ZRapport {
-    #[serde(rename = "sequenceNumber")]
-    pub sequence_number: u32,
-    #[serde(rename = "storeNumber")]
-    pub store_number: u32,
-    #[serde(rename = "cashRegisterNumber")]
-    pub cash_register_number: u32,
-    #[serde(rename = "firstReceipt")]
-    pub first_receipt: u32,
-    #[serde(rename = "lastReceipt")]
-    pub last_receipt: u32,
-    #[serde(rename = "dateCreated")]
-    pub date_created: String,
-    #[serde(rename = "companyName")]
-    pub company_name: String,
-    #[serde(rename = "corporateIdentity")]
-    pub corporate_identity: String,
-
-
-    #[serde(rename = "zReportTransactions")]
-    pub z_report_transactions: Vec<ZRapportTrans>,
-
-    #[serde(flatten)]
-    pub other: HashMap<String, Value>,
-}
-impl ZRapport {
-    pub fn datum(&self) -> String {
-        self.date_created[0..10].to_string()
-    }
-
-    pub fn konto(&self, account: u16) -> Decimal {
-        self.z_report_transactions.iter()
-            .filter(|tr| tr.account_number == account)
-            .map(|tr| tr.amount)
-            .sum()
-    }
-
-    pub fn verifikatnamn(&self) -> String {
-        format!("Z, Bu: {} Ka: {} Nr: {} Kv: {} - {}",
-            self.store_number,
-            self.cash_register_number,
-            self.sequence_number,
-            self.first_receipt,
-            self.last_receipt
-        )
+/// Z-Rapporterna i sidor från listningen.
+pub fn rapporter_fran_sidor(sidor: Vec<Value>) -> Result<Vec<ZRapport>, Error> {
+    let mut result = Vec::new();
+    for sida in sidor {
+        let sida = serde_json::from_value::<ZRapportListResponse>(sida).map_err(Error::Json)?;
+        result.extend(sida.items);
     }
+    Ok(result)
 }
 
-
 #[derive(Deserialize)]
 pub struct ZRapportListResponse {
-    #[serde(rename = "metaInformation")]
-    pub meta_information: EasyCashierMetaInformation,
     pub items: Vec<ZRapport>,
 }
 
 
 impl EasyCashier {
     pub fn login(
+        agent: Agent,
         base_url: &str,
         username: &str,
         password: &str,
@@ -107,7 +57,8 @@ impl EasyCashier {
         let mut body = std::collections::HashMap::new();
         body.insert("username", username);
         body.insert("password", password);
-        let res = ureq::post(url)
+        let res = agent
+            .post(url)
             .header(ACCEPT, APPLICATION_JSON)
             .send_json(&body)?
             .body_mut()
@@ -124,17 +75,15 @@ impl EasyCashier {
             .or(default_company)
             .unwrap_or("".to_string());
         Ok(EasyCashier {
+            agent,
             company,
             base_url: base_url.into(),
             token: token.into(),
         })
     }
 
-    pub fn zrapporter(
-        &self,
-        date: &DateRequest,
-        page: &PageReq,
-    ) -> Result<ZRapportListResponse, Error> {
+    /// En sida ur listningen av Z-Rapporter som EasyCashier skickade den.
+    pub fn zrapporter_json(&self, date: &DateRequest, page: &PageReq) -> Result<Value, Error> {
         let date_params = format!(
             "dateSelectionType={}&startDate={}&stopDate={}",
             date.date_type,
@@ -149,26 +98,65 @@ impl EasyCashier {
             "{}/v1/company/{}/zReport?{}&{}",
             self.base_url, self.company, page_params, date_params
         );
-        ureq::get(url)
+        self.agent.get(url)
             .header(ACCEPT, APPLICATION_JSON)
             .header(USER_AGENT, DEFAULT_USER_AGENT)
             .header("X-Auth-Token", &self.token)
             .call()?
             .body_mut()
-            .read_json::<ZRapportListResponse>()
+            .read_json::<Value>()
+            .map_err(Error::from)
     }
 
-    pub fn zrapport_pdf(&self, rapport: &ZRapport) -> Result<(Vec<u8>, String), Error> {
-        let url = format!(
-            "{}/v1/company/{}/zReport/{}/{}/{}/pdf",
+    /// Alla sidor i listningen av Z-Rapporter inom datumintervallet.
+    pub fn zrapporter_sidor(&self, date_req: &DateRequest) -> Result<Vec<Value>, Error> {
+        let mut page = PageReq { page: 1, size: 100 };
+        let mut result = Vec::new();
+        loop {
+            let sida = self.zrapporter_json(date_req, &page)?;
+            let tom = sida["items"].as_array().is_none_or(|items| items.is_empty());
+            let meta = serde_json::from_value::<EasyCashierMetaInformation>(sida["metaInformation"].clone())
+                .map_err(Error::Json)?;
+            result.push(sida);
+            if tom || page.page >= meta.total_pages {
+                break;
+            }
+            page.page += 1;
+        }
+        Ok(result)
+    }
+
+    fn zrapport_url(&self, id: &ZRapportId) -> String {
+        format!(
+            "{}/v1/company/{}/zReport/{}/{}/{}",
             self.base_url,
             self.company,
-            rapport.store_number,
-            rapport.cash_register_number,
-            rapport.sequence_number
-        );
+            id.store_number,
+            id.cash_register_number,
+            id.sequence_number
+        )
+    }
+
+    /// En Z-Rapport som EasyCashier skickade den.
+    pub fn zrapport_json(&self, id: &ZRapportId) -> Result<Value, Error> {
+        self.agent.get(self.zrapport_url(id))
+            .header(ACCEPT, APPLICATION_JSON)
+            .header(USER_AGENT, DEFAULT_USER_AGENT)
+            .header("X-Auth-Token", &self.token)
+            .call()?
+            .body_mut()
+            .read_json::<Value>()
+            .map_err(Error::from)
+    }
+
+    pub fn zrapport(&self, id: &ZRapportId) -> Result<ZRapport, Error> {
+        serde_json::from_value(self.zrapport_json(id)?).map_err(Error::Json)
+    }
+
+    pub fn zrapport_pdf(&self, rapport: &ZRapport) -> Result<(Vec<u8>, String), Error> {
+        let url = format!("{}/pdf", self.zrapport_url(&rapport.id()));
 
-        let pdf = ureq::get(url)
+        let pdf = self.agent.get(url)
             .header(ACCEPT, "application/pdf")
             .header(USER_AGENT, DEFAULT_USER_AGENT)
             .header("X-Auth-Token", &self.token)
@@ -185,50 +173,20 @@ impl EasyCashier {
     }
 }
 
-#[derive(Debug)]
-pub struct DateRequest {
-    pub start_date: NaiveDate,
-    pub end_date: NaiveDate,
-    pub date_type: String,
-}
+impl ZReportSource for EasyCashier {
+    fn company(&self) -> &str {
+        &self.company
+    }
 
-impl DateRequest {
-    pub fn new(start_date: &Option<NaiveDate>, end_date: &Option<NaiveDate>) -> Self {
-        let today = chrono::Local::now().date_naive();
-        let (start_date, end_date) = {
-            if let Some(start_date) = start_date {
-                if let Some(end_date) = end_date {
-                    (*start_date, *end_date)
-                } else {
-                    (*start_date, today)
-                }
-            } else if let Some(end_date) = end_date {
-                if today < *end_date {
-                    (today, *end_date)
-                } else {
-                    (*end_date, *end_date)
-                }
-            } else {
-                (today, today)
-            }
-        };
-
-        let date_type = {
-            if start_date == end_date {
-                if start_date == today {
-                    "today"
-                } else {
-                    "date"
-                }
-            } else {
-                "interval"
-            }
-        };
+    fn list_reports(&self, date_req: &DateRequest) -> Result<Vec<ZRapport>, Error> {
+        rapporter_fran_sidor(self.zrapporter_sidor(date_req)?)
+    }
 
-        Self {
-            start_date,
-            end_date,
-            date_type: date_type.to_string(),
-        }
+    fn report(&self, id: &ZRapportId) -> Result<ZRapport, Error> {
+        self.zrapport(id)
+    }
+
+    fn document(&self, rapport: &ZRapport) -> Result<(Vec<u8>, String), Error> {
+        self.zrapport_pdf(rapport)
     }
 }
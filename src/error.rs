@@ -0,0 +1,39 @@
+//! Fel från kassasystem och bokföringssystem.
+
+/// Fel från ett kassasystem eller bokföringssystem, se `backend`.
+#[derive(Debug)]
+pub enum Error {
+    /// Anropet misslyckades, t.ex. nätverksfel eller felstatus från servern.
+    Http(ureq::Error),
+    /// Svaret kunde inte tolkas.
+    Json(serde_json::Error),
+    /// Övriga fel med meddelande.
+    Other(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "{}", err),
+            Error::Json(err) => write!(f, "ogiltigt svar: {}", err),
+            Error::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ureq::Error> for Error {
+    fn from(err: ureq::Error) -> Self {
+        match err {
+            ureq::Error::Json(err) => Error::Json(err),
+            err => Error::Http(err),
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
@@ -0,0 +1,487 @@
+//! Verifikat i Fortnox (API v3).
+//!
+//! Verifikaten skapas från samma `CreateJournal` som för Bokio och
+//! översätts till och från Fortnox format. Z-Rapportens PDF laddas upp
+//! till inkorgen för verifikat och kopplas sedan till verifikatet.
+//! Redan bokförda rapporter känns igen på verifikatets beskrivning.
+
+use crate::backend::LedgerSink;
+use crate::error::Error;
+use crate::ledger::{
+    Account, CreateJournal, FiscalYear, JournalEntry, JournalEntryAccount, JournalEntryNumber,
+};
+use crate::utils::{APPLICATION_JSON, PageReq};
+use chrono::NaiveDate;
+use http::header::{ACCEPT, AUTHORIZATION};
+use mime::Mime;
+use multipart::client::lazy::Multipart;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+use ureq::Agent;
+
+pub const FORTNOX_API_URL: &str = "https://api.fortnox.se/3";
+
+/// Inkorgen för verifikatunderlag i Fortnox arkiv.
+const INBOX_VOUCHERS: &str = "inbox_v";
+
+/// Antal försök för ett anrop som Fortnox svarar 429 Too Many Requests på.
+const MAX_FORSOK: u32 = 5;
+
+#[derive(Debug, Deserialize)]
+struct MetaInformation {
+    #[serde(rename = "@TotalPages")]
+    total_pages: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CompanyInformation {
+    organization_number: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CompanyInformationResponse {
+    company_information: CompanyInformation,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FinancialYear {
+    id: u32,
+    from_date: NaiveDate,
+    to_date: NaiveDate,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FinancialYearListing {
+    financial_years: Vec<FinancialYear>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LockedPeriod {
+    end_date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LockedPeriodResponse {
+    locked_period: LockedPeriod,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct FortnoxAccount {
+    number: i32,
+    description: String,
+    active: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AccountListing {
+    accounts: Vec<FortnoxAccount>,
+    #[serde(rename = "MetaInformation")]
+    meta_information: MetaInformation,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct VoucherRow {
+    account: i32,
+    #[serde(with = "crate::amount")]
+    debit: Decimal,
+    #[serde(with = "crate::amount")]
+    credit: Decimal,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    transaction_information: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Voucher {
+    description: String,
+    transaction_date: NaiveDate,
+    voucher_series: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    voucher_number: Option<u32>,
+    /// Räkenskapsårets id i Fortnox.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    year: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    voucher_rows: Vec<VoucherRow>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct VoucherWrapper {
+    voucher: Voucher,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VoucherListing {
+    vouchers: Vec<Voucher>,
+    #[serde(rename = "MetaInformation")]
+    meta_information: MetaInformation,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ArchiveFile {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ArchiveFileResponse {
+    file: ArchiveFile,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct VoucherFileConnection {
+    file_id: String,
+    voucher_number: String,
+    voucher_series: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct VoucherFileConnectionWrapper {
+    voucher_file_connection: VoucherFileConnection,
+}
+
+impl Voucher {
+    /// Verifikatet som `JournalEntry`. Id är `ÅR:SERIE:NUMMER` eftersom
+    /// verifikatnummer bara är unika inom ett räkenskapsår.
+    ///
+    /// Fortnox API säger inte vilket verifikat som rättar ett annat, så
+    /// verifikatet är aldrig markerat som makulerat. En Z-Rapport vars
+    /// verifikat rättats i Fortnox räknas därför fortfarande som bokförd.
+    fn journal_entry(self) -> Result<JournalEntry, String> {
+        let (Some(year), Some(number)) = (self.year, self.voucher_number) else {
+            return Err(format!("verifikat {:?} saknar nummer", self.description));
+        };
+        Ok(JournalEntry {
+            id: format!("{}:{}:{}", year, self.voucher_series, number),
+            title: self.description,
+            journal_entry_number: JournalEntryNumber {
+                series: self.voucher_series,
+                separator: String::new(),
+                number,
+            },
+            date: self.transaction_date,
+            items: self
+                .voucher_rows
+                .into_iter()
+                .zip(1..)
+                .map(|(row, id)| JournalEntryAccount {
+                    id,
+                    account: row.account,
+                    debit: row.debit,
+                    credit: row.credit,
+                    description: row.transaction_information,
+                })
+                .collect(),
+            reversing_journal_entry_id: None,
+            reversed_by_journal_entry_id: None,
+        })
+    }
+}
+
+fn fel(msg: String) -> Error {
+    Error::Other(msg)
+}
+
+pub struct Fortnox {
+    /// Agent för alla anrop, kan ersättas för inspelning och uppspelning.
+    pub agent: Agent,
+    base_url: String,
+    auth_header: String,
+    /// Verifikatserie för Z-Rapporter.
+    pub voucher_series: String,
+    /// Antal verifikat per sida vid listning.
+    pub page_size: u32,
+    /// Väntetid innan ett anrop görs om efter 429 Too Many Requests. Fortnox
+    /// tillåter 25 anrop per 5 sekunder.
+    pub rate_limit_wait: Duration,
+}
+
+impl Fortnox {
+    pub fn new(base_url: &str, token: &str) -> Self {
+        Self {
+            agent: Agent::new_with_defaults(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            auth_header: format!("Bearer {}", token),
+            voucher_series: "A".to_string(),
+            page_size: 100,
+            rate_limit_wait: Duration::from_secs(5),
+        }
+    }
+
+    /// Gör om anropet så länge Fortnox svarar 429, högst `MAX_FORSOK` gånger.
+    fn med_omforsok<T>(&self, anrop: impl Fn() -> Result<T, Error>) -> Result<T, Error> {
+        let mut forsok = 1;
+        loop {
+            match anrop() {
+                Err(Error::Http(ureq::Error::StatusCode(429))) if forsok < MAX_FORSOK => {
+                    std::thread::sleep(self.rate_limit_wait);
+                    forsok += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        self.med_omforsok(|| {
+            self.agent
+                .get(format!("{}{}", self.base_url, path))
+                .header(ACCEPT, APPLICATION_JSON)
+                .header(AUTHORIZATION, &self.auth_header)
+                .call()?
+                .body_mut()
+                .read_json::<T>()
+                .map_err(Error::from)
+        })
+    }
+
+    fn post<T: serde::de::DeserializeOwned>(&self, path: &str, body: &impl Serialize) -> Result<T, Error> {
+        self.med_omforsok(|| {
+            self.agent
+                .post(format!("{}{}", self.base_url, path))
+                .header(ACCEPT, APPLICATION_JSON)
+                .header(AUTHORIZATION, &self.auth_header)
+                .send_json(body)?
+                .body_mut()
+                .read_json::<T>()
+                .map_err(Error::from)
+        })
+    }
+
+    fn financial_years(&self) -> Result<Vec<FinancialYear>, Error> {
+        self.get::<FinancialYearListing>("/financialyears")
+            .map(|lst| lst.financial_years)
+    }
+
+    /// Räkenskapsåret som `date` tillhör.
+    fn financial_year(&self, date: NaiveDate) -> Result<u32, Error> {
+        self.financial_years()?
+            .iter()
+            .find(|fy| fy.from_date <= date && date <= fy.to_date)
+            .map(|fy| fy.id)
+            .ok_or_else(|| fel(format!("inget räkenskapsår för {}", date)))
+    }
+
+    fn vouchers(&self, year: u32, start_date: Option<NaiveDate>, end_date: Option<NaiveDate>) -> Result<Vec<Voucher>, Error> {
+        let mut filter = format!("financialyear={}", year);
+        if let Some(start_date) = start_date {
+            filter += &format!("&fromdate={}", start_date.format("%Y-%m-%d"));
+        }
+        if let Some(end_date) = end_date {
+            filter += &format!("&todate={}", end_date.format("%Y-%m-%d"));
+        }
+
+        let mut page = PageReq { page: 1, size: self.page_size };
+        let mut result = Vec::new();
+        loop {
+            let lst = self.get::<VoucherListing>(&format!(
+                "/vouchers?{}&page={}&limit={}",
+                filter, page.page, page.size
+            ))?;
+            let empty = lst.vouchers.is_empty();
+            result.extend(lst.vouchers);
+            if empty || page.page >= lst.meta_information.total_pages {
+                break;
+            }
+            page.page += 1;
+        }
+        Ok(result)
+    }
+
+    /// Hämta ett verifikat med rader, listningen innehåller inte raderna.
+    fn voucher(&self, year: u32, series: &str, number: u32) -> Result<Voucher, Error> {
+        self.get::<VoucherWrapper>(&format!(
+            "/vouchers/{}/{}?financialyear={}",
+            series, number, year
+        ))
+        .map(|v| v.voucher)
+    }
+}
+
+impl LedgerSink for Fortnox {
+    fn name(&self) -> &str {
+        "Fortnox"
+    }
+
+    fn organization_number(&self) -> Result<String, Error> {
+        self.get::<CompanyInformationResponse>("/companyinformation")
+            .map(|c| c.company_information.organization_number)
+    }
+
+    /// Fortnox har inga stängda räkenskapsår i API:t, bara en låst period
+    /// som gäller alla år.
+    fn fiscal_years(&self) -> Result<Vec<FiscalYear>, Error> {
+        let locked_until = self
+            .get::<LockedPeriodResponse>("/settings/lockedperiod")?
+            .locked_period
+            .end_date;
+        Ok(self
+            .financial_years()?
+            .into_iter()
+            .map(|fy| FiscalYear {
+                id: fy.id.to_string(),
+                start_date: fy.from_date,
+                end_date: fy.to_date,
+                status: "open".to_string(),
+                locked_until,
+            })
+            .collect())
+    }
+
+    fn chart_of_accounts(&self) -> Result<Vec<Account>, Error> {
+        let mut page = PageReq { page: 1, size: 500 };
+        let mut result = Vec::new();
+        loop {
+            let lst = self.get::<AccountListing>(&format!("/accounts?page={}&limit={}", page.page, page.size))?;
+            let empty = lst.accounts.is_empty();
+            result.extend(lst.accounts.into_iter().map(|a| Account {
+                account: a.number,
+                name: a.description,
+                is_active: a.active,
+            }));
+            if empty || page.page >= lst.meta_information.total_pages {
+                break;
+            }
+            page.page += 1;
+        }
+        Ok(result)
+    }
+
+    /// Verifikat i serien för Z-Rapporter, från alla räkenskapsår som
+    /// överlappar intervallet. Verifikaten tas direkt ur listningen, som har
+    /// serie, nummer, beskrivning och datum men inte raderna. Att hämta varje
+    /// verifikat skulle snabbt nå Fortnox gräns på 25 anrop per 5 sekunder,
+    /// så raderna hämtas bara med `entry` där de behövs.
+    fn list_entries(
+        &self,
+        start_date: Option<NaiveDate>,
+        end_date: Option<NaiveDate>,
+        title_prefix: Option<&str>,
+    ) -> Result<Vec<JournalEntry>, Error> {
+        let mut result = Vec::new();
+        for fy in self.financial_years()? {
+            if start_date.is_some_and(|d| fy.to_date < d) || end_date.is_some_and(|d| d < fy.from_date) {
+                continue;
+            }
+            for mut voucher in self.vouchers(fy.id, start_date, end_date)? {
+                if voucher.voucher_series != self.voucher_series
+                    || title_prefix.is_some_and(|p| !voucher.description.starts_with(p))
+                {
+                    continue;
+                }
+                voucher.year = voucher.year.or(Some(fy.id));
+                match voucher.journal_entry() {
+                    Ok(entry) => result.push(entry),
+                    Err(err) => eprintln!("Varning: Fortnox: {}", err),
+                }
+            }
+        }
+        // Senaste verifikatet först, som i Bokio
+        result.sort_by_key(|e| std::cmp::Reverse(e.date));
+        Ok(result)
+    }
+
+    /// Hämta verifikatet med rader, listningen innehåller inte raderna.
+    fn entry(&self, entry: &JournalEntry) -> Result<JournalEntry, Error> {
+        let year = entry
+            .id
+            .split(':')
+            .next()
+            .and_then(|y| y.parse::<u32>().ok())
+            .ok_or_else(|| fel(format!("verifikat {}: saknar räkenskapsår", entry.id)))?;
+        let mut voucher = self.voucher(year, &entry.journal_entry_number.series, entry.journal_entry_number.number)?;
+        voucher.year = voucher.year.or(Some(year));
+        voucher.journal_entry().map_err(fel)
+    }
+
+    fn create_entry(&self, entry: &CreateJournal) -> Result<JournalEntry, Error> {
+        let year = self.financial_year(entry.date)?;
+        let voucher = VoucherWrapper {
+            voucher: Voucher {
+                description: entry.title.clone(),
+                transaction_date: entry.date,
+                voucher_series: self.voucher_series.clone(),
+                voucher_number: None,
+                year: None,
+                voucher_rows: entry
+                    .items
+                    .iter()
+                    .map(|item| VoucherRow {
+                        account: item.account,
+                        debit: item.debit,
+                        credit: item.credit,
+                        transaction_information: item.description.clone(),
+                    })
+                    .collect(),
+            },
+        };
+        let mut created = self
+            .post::<VoucherWrapper>(&format!("/vouchers?financialyear={}", year), &voucher)?
+            .voucher;
+        created.year = created.year.or(Some(year));
+        created
+            .journal_entry()
+            .map_err(fel)
+    }
+
+    /// Ladda upp filen till inkorgen för verifikat och koppla den till verifikatet.
+    fn attach_file(&self, filename: &str, content_type: &str, entry: &JournalEntry) -> Result<(), Error> {
+        let mut m = Multipart::new();
+        let file = std::fs::File::open(filename)
+            .map_err(|err| fel(format!("Kunde inte öppna {}: {}", filename, err)))?;
+        let basename = std::path::Path::new(filename)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| fel(format!("Ogiltigt filnamn {}", filename)))?
+            .to_string();
+        m.add_stream("file", file, Some(basename), Mime::from_str(content_type).ok());
+
+        let mut prepared = m
+            .prepare()
+            .map_err(|err| fel(format!("Kunde inte läsa {}: {}", filename, err.error)))?;
+        let mut vec: Vec<u8> = Vec::new();
+        std::io::copy(&mut prepared, &mut vec)
+            .map_err(|err| fel(format!("Kunde inte läsa {}: {}", filename, err)))?;
+        let boundary = prepared.boundary();
+        let file = self
+            .med_omforsok(|| {
+                self.agent
+                    .post(format!("{}/inbox?path={}", self.base_url, INBOX_VOUCHERS))
+                    .content_type(format!("multipart/form-data; boundary={}", boundary))
+                    .header(ACCEPT, APPLICATION_JSON)
+                    .header(AUTHORIZATION, &self.auth_header)
+                    .send(&vec)?
+                    .body_mut()
+                    .read_json::<ArchiveFileResponse>()
+                    .map_err(Error::from)
+            })?
+            .file;
+
+        let year = entry.id.split(':').next().unwrap_or_default();
+        let connection = VoucherFileConnectionWrapper {
+            voucher_file_connection: VoucherFileConnection {
+                file_id: file.id,
+                voucher_number: entry.journal_entry_number.number.to_string(),
+                voucher_series: entry.journal_entry_number.series.clone(),
+            },
+        };
+        self.post::<serde_json::Value>(&format!("/voucherfileconnections?financialyear={}", year), &connection)
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,118 @@
+use crate::backend::LedgerSink;
+use crate::error::Error;
+use crate::ledger::JournalEntry;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Vid inkrementell synkning hämtas verifikat med datum från så här många
+/// dagar före förra synkningen, för att få med verifikat som bokförts i
+/// efterhand med ett något tidigare datum.
+pub const SYNC_MARGIN_DAYS: u64 = 14;
+
+/// Lokal cache av verifikat från Bokio.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JournalCache {
+    /// Företaget i bokföringssystemet som cachen gäller, t.ex.
+    /// `bokio_5560360793`.
+    #[serde(rename = "ledgerId", alias = "companyId")]
+    ledger_id: String,
+    #[serde(rename = "titlePrefix")]
+    title_prefix: String,
+    /// Cachen innehåller alla verifikat från och med detta datum.
+    #[serde(rename = "syncedFrom")]
+    synced_from: NaiveDate,
+    #[serde(rename = "lastSync")]
+    pub last_sync: DateTime<Utc>,
+    pub entries: Vec<JournalEntry>,
+}
+
+impl JournalCache {
+    fn load(filename: &str) -> Option<Self> {
+        let json = std::fs::read(filename).ok()?;
+        serde_json::from_slice(&json)
+            .inspect_err(|e| eprintln!("{}: ogiltig cache, hämtar om: {}", filename, e))
+            .ok()
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(self).unwrap();
+        std::fs::write(filename, json).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    /// Läs cachen från fil och hämta nya verifikat från bokföringen. Om
+    /// cachen täcker `start_date` hämtas bara verifikat med datum från
+    /// `SYNC_MARGIN_DAYS` dagar före förra synkningen, annars allt från
+    /// `start_date`. Bokio kan inte filtrera på när verifikat skapats, så
+    /// verifikat som bokförts i efterhand med ett äldre datum än så kommer bara
+    /// med vid `refresh`, då hela cachen byggs om. Cachen byggs också om om den
+    /// gäller ett annat företag eller prefix.
+    pub fn sync(
+        filename: &str,
+        ledger: &dyn LedgerSink,
+        ledger_id: &str,
+        title_prefix: &str,
+        start_date: NaiveDate,
+        refresh: bool,
+    ) -> Result<Self, Error> {
+        let cache = Some(refresh)
+            .filter(|refresh| !refresh)
+            .and_then(|_| Self::load(filename))
+            .filter(|c| c.ledger_id == ledger_id && c.title_prefix == title_prefix);
+        let (mut cache, from) = match cache {
+            Some(cache) if cache.synced_from <= start_date => {
+                let from = cache
+                    .last_sync
+                    .date_naive()
+                    .checked_sub_days(Days::new(SYNC_MARGIN_DAYS))
+                    .unwrap_or(cache.synced_from)
+                    .max(cache.synced_from);
+                (cache, from)
+            }
+            Some(cache) => (cache, start_date),
+            None => (
+                JournalCache {
+                    ledger_id: ledger_id.to_string(),
+                    title_prefix: title_prefix.to_string(),
+                    synced_from: start_date,
+                    last_sync: Utc::now(),
+                    entries: Vec::new(),
+                },
+                start_date,
+            ),
+        };
+
+        let prefix = Some(title_prefix).filter(|p| !p.is_empty());
+        let entries = ledger.list_entries(Some(from), None, prefix)?;
+        // Verifikat från `from` ersätts så att även borttagna verifikat försvinner
+        cache.entries.retain(|e| e.date < from);
+        cache.synced_from = cache.synced_from.min(start_date);
+        for entry in entries {
+            cache.upsert(entry);
+        }
+        cache.last_sync = Utc::now();
+        Ok(cache)
+    }
+
+    /// Lägg till eller ersätt ett verifikat. Om verifikatet vänder ett
+    /// annat verifikat markeras det vända verifikatet.
+    pub fn upsert(&mut self, entry: JournalEntry) {
+        if let Some(reversed) = &entry.reversing_journal_entry_id
+            && let Some(original) = self.entries.iter_mut().find(|e| &e.id == reversed)
+        {
+            original.reversed_by_journal_entry_id = Some(entry.id.clone());
+        }
+
+        match self.entries.iter_mut().find(|e| e.id == entry.id) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    /// Verifikat med datum mellan `start_date` och `end_date`.
+    pub fn entries_between(&self, start_date: NaiveDate, end_date: NaiveDate) -> Vec<&JournalEntry> {
+        self.entries
+            .iter()
+            .filter(|e| start_date <= e.date && e.date <= end_date)
+            .collect()
+    }
+}
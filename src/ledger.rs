@@ -0,0 +1,153 @@
+//! Verifikat, konton och räkenskapsår som delas av bokföringssystemen.
+//!
+//! Typerna följer Bokios JSON-format, som också används i cachen av
+//! verifikat och i de JSON-filer som sparas bredvid underlagen. Andra
+//! bokföringssystem översätter till dessa typer, se `fortnox`.
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JournalEntryAccount {
+    pub id: i64,
+    pub account: i32,
+    #[serde(with = "crate::amount")]
+    pub debit: Decimal,
+    #[serde(with = "crate::amount")]
+    pub credit: Decimal,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Verifikatnummer, serie följt av löpnummer, t.ex. `V123` eller
+/// `V-2024-12` där serien är `V-2024`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct JournalEntryNumber {
+    pub series: String,
+    /// Tecken mellan serie och löpnummer, t.ex. `-`.
+    pub separator: String,
+    pub number: u32,
+}
+
+impl FromStr for JournalEntryNumber {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let (prefix, number) = s.split_at(split);
+        let number = number
+            .parse::<u32>()
+            .map_err(|_| format!("ogiltigt verifikatnummer {:?}", s))?;
+        let series = prefix.trim_end_matches(['-', ' ']);
+        Ok(JournalEntryNumber {
+            series: series.to_string(),
+            separator: prefix[series.len()..].to_string(),
+            number,
+        })
+    }
+}
+
+impl TryFrom<String> for JournalEntryNumber {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<JournalEntryNumber> for String {
+    fn from(n: JournalEntryNumber) -> Self {
+        n.to_string()
+    }
+}
+
+impl std::fmt::Display for JournalEntryNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.series, self.separator, self.number)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[allow(unused)]
+pub struct JournalEntry {
+    pub id: String,
+    pub title: String,
+    #[serde(rename = "journalEntryNumber")]
+    pub journal_entry_number: JournalEntryNumber,
+    pub date: NaiveDate,
+    pub items: Vec<JournalEntryAccount>,
+    #[serde(rename = "reversingJournalEntryId")]
+    pub reversing_journal_entry_id: Option<String>,
+    #[serde(rename = "reversedByJournalEntryId")]
+    pub reversed_by_journal_entry_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateJournalAccount {
+    pub account: i32,
+    #[serde(with = "crate::amount")]
+    pub debit: Decimal,
+    #[serde(with = "crate::amount")]
+    pub credit: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateJournal {
+    pub title: String,
+    pub date: NaiveDate,
+    pub items: Vec<CreateJournalAccount>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(unused)]
+pub struct FiscalYear {
+    pub id: String,
+    #[serde(rename = "startDate")]
+    pub start_date: NaiveDate,
+    #[serde(rename = "endDate")]
+    pub end_date: NaiveDate,
+    /// `open` eller `closed`
+    pub status: String,
+    /// Perioder till och med detta datum är låsta, t.ex. efter momsredovisning.
+    #[serde(rename = "lockedUntil")]
+    pub locked_until: Option<NaiveDate>,
+}
+
+impl FiscalYear {
+    /// Returnerar en förklaring om det inte går att bokföra på `date`
+    /// i detta räkenskapsår, annars `None`.
+    pub fn stangd(&self, date: NaiveDate) -> Option<String> {
+        if self.status != "open" {
+            return Some(format!(
+                "räkenskapsåret {} - {} är stängt",
+                self.start_date, self.end_date
+            ));
+        }
+
+        match self.locked_until {
+            Some(locked_until) if date <= locked_until => {
+                Some(format!("perioden är låst t.o.m. {}", locked_until))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start_date <= date && date <= self.end_date
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(unused)]
+pub struct Account {
+    pub account: i32,
+    pub name: String,
+    #[serde(rename = "isActive")]
+    pub is_active: bool,
+}
@@ -1,18 +1,61 @@
-use crate::bokio::{BOKIO_API_URL, Bokio, CreateJournal, CreateJournalAccount, JournalEntry};
-use crate::easycashier::{DateRequest, EASYCASHIER_URL, EasyCashier, ZRapport};
+use crate::bokio::{BOKIO_API_URL, Bokio};
+use crate::backend::{LedgerSink, ZReportSource};
+use crate::easycashier::{EASYCASHIER_URL, EasyCashier};
+use crate::error::Error;
+use crate::ledger::{Account, CreateJournal, CreateJournalAccount, FiscalYear, JournalEntry};
+use crate::zrapport::{DateRequest, ZRapport, ZRapportId};
+use crate::fortnox::{FORTNOX_API_URL, Fortnox};
 use chrono::Days;
 use chrono::naive::NaiveDate;
 use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::Value;
 use std::io::{Write};
-use ureq::Error;
-use utils::{PageReq, read_password_trim, read_prompt_trim, to_date, format_local_date};
-use crate::utils::format_orgnr;
+use std::str::FromStr;
+use std::path::Path;
+use utils::{read_password_trim, read_prompt_trim, to_date, format_local_date};
+use crate::journal_cache::JournalCache;
+use crate::mapping::Mapping;
+use crate::orgnr::Orgnr;
+use crate::schema::{SchemaAvvikelse, SchemaBaseline};
+use crate::sequence::Kassa;
+use crate::settlement::Utbetalning;
+use crate::title::{LEGACY_TITLE_TEMPLATE, TitleTemplate};
 
+mod amount;
+mod backend;
 mod bokio;
 mod easycashier;
+mod error;
+mod fortnox;
+mod journal_cache;
+mod ledger;
+mod mapping;
+mod orgnr;
+mod recording;
+mod schema;
+mod sequence;
+mod settlement;
+mod show;
+mod title;
+mod tui;
 mod utils;
+mod zrapport;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(PartialEq)]
+enum Kommando {
+    Importera,
+    SchemaCheck,
+    AuditSequence,
+    Visa,
+    Utbetalningar,
+}
 
 struct Cli {
+    kommando: Kommando,
     orgnummer: String,
     easycashier_url: String,
     easycashier_username: String,
@@ -20,105 +63,675 @@ struct Cli {
     bokio_api_url: String,
     bokio_api_token: String,
     bokio_company_id: String,
+    /// Bokföringssystem att skapa verifikat i.
+    ledger: Bokforing,
+    fortnox_api_url: String,
+    fortnox_access_token: String,
+    /// Verifikatserie i Fortnox för Z-Rapporter.
+    fortnox_voucher_series: String,
     start_date: Option<NaiveDate>,
     end_date: Option<NaiveDate>,
+    reject_personnummer: bool,
+    allow_company_mismatch: bool,
+    /// Rapporter som stängs före denna timme (lokal tid) bokförs på föregående dag.
+    brytpunkt: u32,
+    /// Avbryt om EasyCashiers JSON avviker från baslinjen.
+    strict_schema: bool,
+    /// Fil med baslinje för schemakontroll, tom för inbyggd baslinje.
+    schema_baseline: String,
+    /// Uppdatera baslinjen med nycklarna i hämtade rapporter (schema-check).
+    update_baseline: bool,
+    /// Fil med kontomappning, tom för ingen mappning.
+    mapping: String,
+    /// Mall för verifikatnamn.
+    title_template: String,
+    /// Antal verifikat per sida vid listning i Bokio.
+    bokio_page_size: u32,
+    /// Importera bara denna rapport i stället för rapporter inom datumintervallet.
+    report: Option<ZRapportId>,
+    /// Första Z-nummer som ska bearbetas.
+    from_seq: Option<u32>,
+    /// Sista Z-nummer som ska bearbetas.
+    to_seq: Option<u32>,
+    /// Bearbeta bara rapporter för denna kassa.
+    kassa: Option<Kassa>,
+    /// Använd radbaserad meny även i terminal.
+    no_tui: bool,
+    /// Fil för lokal cache av verifikat, tom för standardnamn.
+    journal_cache: String,
+    /// Katalog där underlag (PDF och JSON) sparas, tom för arbetskatalogen.
+    output_dir: String,
+    /// Bygg om cachen av verifikat.
+    refresh: bool,
+    /// Utdataformat för listningen. CSV och JSON listar bara rapporterna.
+    format: Format,
+    /// Katalog att spela in HTTP-trafik till.
+    record: String,
+    /// Katalog med inspelad HTTP-trafik som används i stället för servrarna.
+    replay: String,
+    /// Avräkningsfil från kortinlösaren (settlement).
+    settlement: String,
+    /// Bankkonto för utbetalningar från kortinlösaren.
+    bankkonto: u16,
+    /// Konto för kortinlösarens avgifter.
+    avgiftskonto: u16,
 }
 
 fn check_arg(name: &str, arg: &str, iter: &mut impl Iterator<Item = String>) -> Option<String> {
     let prefix = "--".to_string() + name;
     if arg == prefix {
         let val = iter.next();
-        return Some(val.expect(&format!("{} expected value", prefix)));
+        return Some(val.unwrap_or_else(|| panic!("{} expected value", prefix)));
     }
 
     let prefix = prefix + "=";
     if let Some(val) = arg.strip_prefix(&prefix) {
         let val = Some(val)
             .filter(|s| !s.is_empty())
-            .expect(&format!("{} expected value", prefix));
+            .unwrap_or_else(|| panic!("{} expected value", prefix));
         return Some(val.to_string());
     }
 
     None
 }
 
+fn check_flag(name: &str, arg: &str) -> bool {
+    arg.strip_prefix("--") == Some(name)
+}
+
+fn parse_orgnr(args: &Cli, orgnr: &str) -> Orgnr {
+    Orgnr::parse(orgnr, !args.reject_personnummer)
+        .inspect_err(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+        .unwrap()
+}
+
 struct RapportImport {
     rapport: ZRapport,
+    titel: String,
+    /// Bokföringsdatum, `None` om rapportens tidpunkt inte kunde tolkas.
+    datum: Option<NaiveDate>,
     verifikat: Option<JournalEntry>,
+    /// Anledning till att rapporten inte kan bokföras (ogiltigt datum, stängt
+    /// räkenskapsår eller låst period).
+    stangd: Option<String>,
+}
+
+impl RapportImport {
+    fn importerbar(&self) -> bool {
+        self.verifikat.is_none() && self.stangd.is_none()
+    }
+}
+
+fn kontrollera_period(date: NaiveDate, fiscal_years: &[FiscalYear]) -> Option<String> {
+    match fiscal_years.iter().find(|fy| fy.contains(date)) {
+        Some(fy) => fy.stangd(date),
+        None => Some(format!("inget räkenskapsår för {}", format_local_date(&date))),
+    }
+}
+
+/// Gemensamt prefix för verifikatnamn med mallen och det äldre formatet.
+fn titelprefix(template: &TitleTemplate) -> String {
+    template
+        .prefix()
+        .chars()
+        .zip(TitleTemplate::legacy().prefix().chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a)
+        .collect()
+}
+
+/// Första datum som behövs i Bokio för att hitta bokförda rapporter,
+/// verifikat kan ha bokförts med datum något före rapportens datum.
+fn bokio_startdatum(date_req: &DateRequest) -> NaiveDate {
+    date_req
+        .start_date
+        .checked_sub_days(Days::new(14))
+        .unwrap_or(date_req.start_date)
+}
+
+/// Datumintervall för att hämta rapporter. Vid urval på Z-nummer utan
+/// startdatum söks rapporter ett år bakåt.
+fn datumintervall(args: &Cli) -> DateRequest {
+    let start_date = args.start_date.or_else(|| {
+        args.from_seq.or(args.to_seq).and_then(|_| {
+            chrono::Local::now()
+                .date_naive()
+                .checked_sub_days(Days::new(366))
+        })
+    });
+    DateRequest::new(&start_date, &args.end_date)
+}
+
+/// Välj rapporter efter kassa och intervall av Z-nummer.
+fn filtrera_rapporter(args: &Cli, rapporter: Vec<ZRapport>) -> Result<Vec<ZRapport>, String> {
+    let rapporter = rapporter
+        .into_iter()
+        .filter(|r| args.kassa.is_none_or(|k| sequence::kassa(r) == k))
+        .filter(|r| args.from_seq.is_none_or(|n| r.sequence_number >= n))
+        .filter(|r| args.to_seq.is_none_or(|n| r.sequence_number <= n))
+        .collect::<Vec<_>>();
+
+    if (args.from_seq.is_some() || args.to_seq.is_some())
+        && sequence::per_kassa(rapporter.iter()).len() > 1
+    {
+        return Err(
+            "Z-nummer gäller per kassa, ange kassa med --cash-register BUTIK:KASSA".to_string(),
+        );
+    }
+
+    Ok(rapporter)
 }
 
 fn hamta_rapporter(
-    args: &Cli,
-    easy: &EasyCashier,
-    bokio: &Bokio,
-) -> Result<(Vec<RapportImport>, DateRequest), Error> {
-    let mut page = PageReq { page: 1, size: 100 };
-    let date_req = DateRequest::new(&args.start_date, &args.end_date);
-    let bokio_start_date = date_req.start_date.checked_sub_days(Days::new(14));
-    let journal = bokio.list_journal(bokio_start_date, Some(date_req.end_date))?;
+    brytpunkt: u32,
+    date_req: &DateRequest,
+    template: &TitleTemplate,
+    company: &Orgnr,
+    ledger: &dyn LedgerSink,
+    cache: &JournalCache,
+    rapporter: Vec<ZRapport>,
+) -> Result<Vec<RapportImport>, Error> {
+    let journal = cache.entries_between(bokio_startdatum(date_req), date_req.end_date);
+    let legacy = TitleTemplate::legacy();
+    let fiscal_years = ledger.fiscal_years()?;
     let mut importer: Vec<RapportImport> = Vec::new();
-    loop {
-        let rapporter = easy.zrapporter(&date_req, &page)?;
-        if rapporter.items.is_empty() {
-            break;
+    for rapport in rapporter {
+        match rapport.orgnr() {
+            Ok(orgnr) if orgnr == *company => {}
+            Ok(orgnr) => eprintln!(
+                "Varning: Z-Rapport {} tillhör {}, inte {}",
+                rapport.sequence_number, orgnr, company
+            ),
+            Err(err) => eprintln!(
+                "Varning: Z-Rapport {}: {}",
+                rapport.sequence_number, err
+            ),
         }
 
-        for rapport in rapporter.items {
-            let title = rapport.verifikatnamn();
-            let verifikat = journal
-                .iter()
-                .find(|e| e.title == title && e.reversed_by_journal_entry_id.is_none());
-            importer.push(RapportImport {
-                rapport,
-                verifikat: verifikat.cloned(),
-            })
-        }
+        let verifikat = journal.iter().copied().find(|e| {
+            e.reversed_by_journal_entry_id.is_none()
+                && (template.matchar(&e.title, &rapport) || legacy.matchar(&e.title, &rapport))
+        });
+        let datum = rapport.datum(brytpunkt);
+        let titel = template.format(&rapport, datum.as_ref().ok().copied());
+        let stangd = match (verifikat, &datum) {
+            (Some(_), _) => None,
+            (None, Ok(date)) => kontrollera_period(*date, &fiscal_years),
+            (None, Err(err)) => Some(err.clone()),
+        };
+        importer.push(RapportImport {
+            rapport,
+            titel,
+            datum: datum.ok(),
+            verifikat: verifikat.cloned(),
+            stangd,
+        })
+    }
 
-        if page.page >= rapporter.meta_information.total_pages {
-            break;
-        }
-        page.page += 1;
+    Ok(importer)
+}
+
+fn las_baslinje(args: &Cli) -> SchemaBaseline {
+    if args.schema_baseline.is_empty() {
+        return SchemaBaseline::default();
+    }
+    // En ny baslinje kan skapas med --update-baseline
+    if args.update_baseline && !std::path::Path::new(&args.schema_baseline).exists() {
+        return SchemaBaseline::default();
     }
 
-    Ok((importer, date_req))
+    SchemaBaseline::load(&args.schema_baseline)
+        .inspect_err(|err| {
+            eprintln!("Kunde inte läsa baslinje: {}", err);
+            std::process::exit(1);
+        })
+        .unwrap()
 }
 
-fn rakna_importerade_rapporter(importer: &Vec<RapportImport>) -> usize {
-    importer.iter().filter(|e| e.verifikat.is_some()).count()
+fn skriv_avvikelser(rapport: Option<u64>, avvikelser: &[SchemaAvvikelse]) {
+    match rapport {
+        Some(nr) => println!("Z-Rapport {}:", nr),
+        None => println!("Z-Rapport utan sequenceNumber:"),
+    }
+    for avvikelse in avvikelser {
+        println!("  {}", avvikelse);
+    }
 }
 
-fn lista_rapporter(importer: &Vec<RapportImport>) {
+/// Skriv ut avvikelser från baslinjen i sidorna från EasyCashier och
+/// returnera antalet rapporter och sidor som avviker.
+fn kontrollera_schema(baslinje: &SchemaBaseline, sidor: &[Value]) -> (usize, usize) {
+    let (mut rapporter, mut antal_sidor) = (0, 0);
+    for (i, sida) in sidor.iter().enumerate() {
+        if let Some(avvikelse) = baslinje.kontrollera_meta(sida) {
+            antal_sidor += 1;
+            println!("Sida {}:", i + 1);
+            println!("  {}", avvikelse);
+        }
+        for r in baslinje.kontrollera_sida(sida) {
+            rapporter += 1;
+            skriv_avvikelser(r.rapport, &r.avvikelser);
+        }
+    }
+    (rapporter, antal_sidor)
+}
+
+/// Hämta rapporterna som EasyCashier skickar dem och avbryt om de avviker
+/// från baslinjen (--strict-schema). Kontrollen görs innan rapporterna
+/// tolkas så att även saknade obligatoriska fält rapporteras.
+fn hamta_med_schemakontroll(args: &Cli, easy: &EasyCashier) -> Result<Vec<ZRapport>, Error> {
+    let baslinje = las_baslinje(args);
+    let (antal, rapporter) = match &args.report {
+        Some(id) => {
+            let json = easy.zrapport_json(id)?;
+            let avvikelser = baslinje.kontrollera(&json);
+            if !avvikelser.is_empty() {
+                skriv_avvikelser(json["sequenceNumber"].as_u64(), &avvikelser);
+            }
+            let rapport = serde_json::from_value::<ZRapport>(json).map_err(Error::Json)?;
+            (usize::from(!avvikelser.is_empty()), vec![rapport])
+        }
+        None => {
+            let sidor = easy.zrapporter_sidor(&datumintervall(args))?;
+            let (rapporter, sidor_som_avviker) = kontrollera_schema(&baslinje, &sidor);
+            (rapporter + sidor_som_avviker, easycashier::rapporter_fran_sidor(sidor)?)
+        }
+    };
+    if antal > 0 {
+        eprintln!(
+            "{} Z-Rapporter eller sidor avviker från baslinjen, avbryter (--strict-schema)",
+            antal
+        );
+        std::process::exit(1);
+    }
+    Ok(rapporter)
+}
+
+fn schema_check(args: &Cli, easy: &EasyCashier) {
+    let date_req = DateRequest::new(&args.start_date, &args.end_date);
+    let sidor = easy
+        .zrapporter_sidor(&date_req)
+        .inspect_err(|err| {
+            eprintln!("Kunde inte hämta Z-Rapporter: {}", err);
+            std::process::exit(1);
+        })
+        .unwrap();
+    let antal_rapporter = sidor
+        .iter()
+        .map(|sida| sida["items"].as_array().map_or(0, Vec::len))
+        .sum::<usize>();
+
+    let mut baslinje = las_baslinje(args);
+    if args.update_baseline {
+        if args.schema_baseline.is_empty() {
+            eprintln!("--update-baseline kräver --schema-baseline FIL");
+            std::process::exit(1);
+        }
+
+        for sida in sidor.iter() {
+            baslinje.uppdatera_sida(sida);
+        }
+        baslinje
+            .save(&args.schema_baseline)
+            .inspect_err(|err| {
+                eprintln!("Kunde inte spara baslinje: {}", err);
+                std::process::exit(1);
+            })
+            .ok();
+        println!(
+            "Baslinjen {} uppdaterad från {} Z-Rapporter",
+            args.schema_baseline, antal_rapporter
+        );
+        return;
+    }
+
+    let (antal, sidor_som_avviker) = kontrollera_schema(&baslinje, &sidor);
     println!(
-        "| ✓ |   NR | DATUM      | {:<39} |     KORT |  KONTANT |   SWISH | VERNR |",
-        "TITEL"
+        "{} av {} Z-Rapporter avviker från baslinjen",
+        antal, antal_rapporter
     );
+    if sidor_som_avviker > 0 {
+        println!("{} sidor har avvikande metaInformation", sidor_som_avviker);
+    }
+    if antal > 0 || sidor_som_avviker > 0 {
+        std::process::exit(2);
+    }
+}
+
+/// Granska Z-nummer och kvitton per kassa och visa vilka saknade
+/// Z-nummer som inte heller har något verifikat i Bokio.
+fn granska_nummerserie(
+    dates: &DateRequest,
+    template: &TitleTemplate,
+    cache: &JournalCache,
+    rapporter: &[ZRapport],
+) {
     println!(
-        "|---|------|------------|-{:-<39}-|----------|----------|---------|-------|",
-        "-"
+        "Granskning av Z-nummer {} - {}",
+        format_local_date(&dates.start_date),
+        format_local_date(&dates.end_date)
     );
+    for (kassa, lst) in sequence::per_kassa(rapporter) {
+        println!(
+            "  {}: Z-nummer {} - {} ({} rapporter)",
+            kassa,
+            lst.first().map_or(0, |r| r.sequence_number),
+            lst.last().map_or(0, |r| r.sequence_number),
+            lst.len()
+        );
+    }
+    println!();
+
+    let legacy = TitleTemplate::legacy();
+    let avvikelser = sequence::granska(rapporter);
+    for avvikelse in avvikelser.iter() {
+        println!("{}", avvikelse);
+        let sequence::Avvikelse::Lucka(lucka) = avvikelse else {
+            continue;
+        };
+
+        let sequence::Kassa { store, register } = lucka.kassa;
+        for nr in lucka.from..=lucka.to {
+            let verifikat = cache.entries.iter().find(|e| {
+                e.reversed_by_journal_entry_id.is_none()
+                    && (template.avser_nummer(&e.title, store, register, nr)
+                        || legacy.avser_nummer(&e.title, store, register, nr))
+            });
+            match verifikat {
+                Some(verifikat) => println!(
+                    "    Nr: {} bokförd i Bokio som {} {}",
+                    nr, verifikat.journal_entry_number, verifikat.date
+                ),
+                None => println!("    Nr: {} saknar verifikat i Bokio", nr),
+            }
+        }
+    }
+
+    println!();
+    println!("{} avvikelser", avvikelser.len());
+    if !avvikelser.is_empty() {
+        std::process::exit(2);
+    }
+}
+
+fn rakna_importerade_rapporter(importer: &[RapportImport]) -> usize {
+    importer.iter().filter(|e| e.verifikat.is_some()).count()
+}
+
+/// Utdataformat för listningen av Z-Rapporter.
+#[derive(PartialEq)]
+enum Format {
+    Tabell,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Tabell),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("{}: förväntade table, csv eller json", s)),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Bokforing {
+    Bokio,
+    Fortnox,
+}
+
+impl FromStr for Bokforing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bokio" => Ok(Bokforing::Bokio),
+            "fortnox" => Ok(Bokforing::Fortnox),
+            _ => Err(format!("{}: förväntade bokio eller fortnox", s)),
+        }
+    }
+}
+
+fn parse_ledger(s: &str) -> Bokforing {
+    s.parse::<Bokforing>()
+        .inspect_err(|err| {
+            eprintln!("--ledger: {}", err);
+            std::process::exit(1);
+        })
+        .unwrap()
+}
+
+fn vernr(e: &RapportImport) -> String {
+    e.verifikat
+        .as_ref()
+        .map_or("".to_string(), |j| j.journal_entry_number.to_string())
+}
+
+fn lista_rapporter(importer: &[RapportImport], mapping: &Mapping, format: &Format) {
+    match format {
+        Format::Tabell => lista_tabell(importer, mapping),
+        Format::Csv => lista_csv(importer, mapping),
+        Format::Json => lista_json(importer, mapping),
+    }
+}
+
+fn lista_tabell(importer: &[RapportImport], mapping: &Mapping) {
+    let bredd = importer
+        .iter()
+        .map(|e| e.titel.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(5);
+    let rubriker = mapping.rubriker();
+    let bredder = rubriker
+        .iter()
+        .map(|r| r.chars().count().max(8))
+        .collect::<Vec<_>>();
+
+    let mut rubrik = format!("| ✓ |   NR | DATUM      | {:<bredd$} |", "TITEL");
+    let mut linje = format!("|---|------|------------|-{:-<bredd$}-|", "-");
+    for (r, b) in rubriker.iter().zip(bredder.iter()) {
+        rubrik += &format!(" {:>b$} |", r);
+        linje += &format!("-{:-<b$}-|", "-");
+    }
+    println!("{} VERNR |", rubrik);
+    println!("{}-------|", linje);
+
     for e in importer {
         let rapport = &e.rapport;
-        let verifikat = &e.verifikat;
-        let title = rapport.verifikatnamn();
-        let datum = rapport.datum();
-        let kort = rapport.konto(1580);
-        let kontant = rapport.konto(1911);
-        let swish = rapport.konto(1932);
-        let vernr = verifikat
-            .clone()
-            .map_or("".to_string(), |j| j.journal_entry_number);
-        let marker = if e.verifikat.is_some() { "✓" } else { " " };
-        println!(
-            "| {} | {:4} | {} | {:<39} | {:8.2} | {:8.2} | {:7.2} | {:<5} |",
-            marker, rapport.sequence_number, datum, title, kort, kontant, swish, vernr
+        let datum = e.datum.as_ref().map_or("?".to_string(), format_local_date);
+        let marker = if e.verifikat.is_some() {
+            "✓"
+        } else if e.stangd.is_some() {
+            "✗"
+        } else {
+            " "
+        };
+        let mut rad = format!(
+            "| {} | {:4} | {:<10} | {:<bredd$} |",
+            marker, rapport.sequence_number, datum, e.titel
         );
+        for (belopp, b) in mapping.belopp(rapport).iter().zip(bredder.iter()) {
+            rad += &format!(" {:>b$.2} |", belopp);
+        }
+        println!("{} {:<5} |", rad, vernr(e));
+    }
+
+    for e in importer.iter().filter(|e| e.verifikat.is_none()) {
+        if let Some(stangd) = &e.stangd {
+            println!(
+                "✗ Z-Rapport {} kan inte bokföras: {}",
+                e.rapport.sequence_number, stangd
+            );
+        }
+    }
+}
+
+/// Fält i CSV citeras om det innehåller avgränsare, citattecken eller radbrytning.
+fn csv_falt(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
     }
 }
 
-fn valj_rapporter(rapporter: &Vec<RapportImport>) -> Vec<u32> {
+fn lista_csv(importer: &[RapportImport], mapping: &Mapping) {
+    let mut rubrik = vec!["BUTIK", "KASSA", "NR", "DATUM", "TITEL"];
+    rubrik.extend(mapping.rubriker());
+    rubrik.extend(["VERNR", "STATUS"]);
+    println!("{}", rubrik.iter().map(|r| csv_falt(r)).collect::<Vec<_>>().join(","));
+
+    for e in importer {
+        let rapport = &e.rapport;
+        let mut rad = vec![
+            rapport.store_number.to_string(),
+            rapport.cash_register_number.to_string(),
+            rapport.sequence_number.to_string(),
+            e.datum.map_or("".to_string(), |d| d.to_string()),
+            e.titel.clone(),
+        ];
+        rad.extend(mapping.belopp(rapport).iter().map(|b| format!("{:.2}", b)));
+        rad.push(vernr(e));
+        rad.push(e.stangd.clone().filter(|_| e.verifikat.is_none()).unwrap_or_default());
+        println!("{}", rad.iter().map(|f| csv_falt(f)).collect::<Vec<_>>().join(","));
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListKolumn<'a> {
+    label: &'a str,
+    #[serde(with = "crate::amount")]
+    amount: Decimal,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ListRad<'a> {
+    store_number: u32,
+    cash_register_number: u32,
+    sequence_number: u32,
+    date: Option<NaiveDate>,
+    title: &'a str,
+    columns: Vec<ListKolumn<'a>>,
+    journal_entry_number: Option<String>,
+    /// Anledning till att rapporten inte kan bokföras.
+    blocked: Option<&'a str>,
+}
+
+fn lista_json(importer: &[RapportImport], mapping: &Mapping) {
+    let rubriker = mapping.rubriker();
+    let rader = importer
+        .iter()
+        .map(|e| ListRad {
+            store_number: e.rapport.store_number,
+            cash_register_number: e.rapport.cash_register_number,
+            sequence_number: e.rapport.sequence_number,
+            date: e.datum,
+            title: &e.titel,
+            columns: rubriker
+                .iter()
+                .zip(mapping.belopp(&e.rapport))
+                .map(|(label, amount)| ListKolumn { label, amount })
+                .collect(),
+            journal_entry_number: e.verifikat.as_ref().map(|j| j.journal_entry_number.to_string()),
+            blocked: e.stangd.as_deref().filter(|_| e.verifikat.is_none()),
+        })
+        .collect::<Vec<_>>();
+    println!("{}", serde_json::to_string_pretty(&rader).unwrap());
+}
+
+/// Formatera nummer som intervall, t.ex. `120-127, 129`.
+fn formatera_nummer(nummer: &[u32]) -> String {
+    let mut nummer = nummer.to_vec();
+    nummer.sort();
+    nummer.dedup();
+    let mut delar: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < nummer.len() {
+        let start = nummer[i];
+        while i + 1 < nummer.len() && nummer[i + 1] == nummer[i] + 1 {
+            i += 1;
+        }
+        if nummer[i] == start {
+            delar.push(start.to_string());
+        } else {
+            delar.push(format!("{}-{}", start, nummer[i]));
+        }
+        i += 1;
+    }
+    delar.join(", ")
+}
+
+/// Tolka ett val av rapporter. Varje ord är ett nummer (`128`), ett
+/// intervall (`120-135`), ett undantag (`!128` eller `!120-125`) eller `alla`.
+/// Består valet bara av undantag utgår det från alla möjliga rapporter.
+fn tolka_val(input: &str, mojliga: &[u32]) -> Result<Vec<u32>, String> {
+    let intervall = |s: &str| -> Option<(u32, u32)> {
+        match s.split_once('-') {
+            Some((a, b)) => Some((a.trim().parse().ok()?, b.trim().parse().ok()?)),
+            None => s.parse().ok().map(|n| (n, n)),
+        }
+    };
+
+    let mut valda: Vec<u32> = Vec::new();
+    let mut undantag: Vec<u32> = Vec::new();
+    let mut bara_undantag = true;
+    for part in input.split_whitespace() {
+        if part == "alla" || part == "*" {
+            valda.extend(mojliga);
+            bara_undantag = false;
+        } else if let Some(rest) = part.strip_prefix('!') {
+            let (from, to) = intervall(rest).ok_or_else(|| part.to_string())?;
+            undantag.extend(mojliga.iter().filter(|n| (from..=to).contains(*n)));
+        } else {
+            let (from, to) = intervall(part).ok_or_else(|| part.to_string())?;
+            let nummer = mojliga
+                .iter()
+                .filter(|n| (from..=to).contains(*n))
+                .copied()
+                .collect::<Vec<_>>();
+            if nummer.is_empty() {
+                return Err(part.to_string());
+            }
+            valda.extend(nummer);
+            bara_undantag = false;
+        }
+    }
+
+    if bara_undantag {
+        valda.extend(mojliga);
+    }
+
+    Ok(mojliga
+        .iter()
+        .filter(|n| valda.contains(n) && !undantag.contains(n))
+        .copied()
+        .collect())
+}
+
+fn las_rad() -> Option<String> {
+    std::io::stdout().flush().unwrap();
+    let mut input = String::new();
+    match std::io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input),
+    }
+}
+
+fn valj_rapporter(rapporter: &[RapportImport]) -> Vec<u32> {
     let mojliga = rapporter
         .iter()
-        .filter(|e| e.verifikat.is_none())
+        .filter(|e| e.importerbar())
         .map(|e| e.rapport.sequence_number)
         .collect::<Vec<_>>();
 
@@ -133,58 +746,70 @@ fn valj_rapporter(rapporter: &Vec<RapportImport>) -> Vec<u32> {
                 mojliga.first().unwrap()
             );
         } else {
-            print!("Importera ([J]a = alla, [N]ej = ingen eller nummer)? ");
+            print!("Importera ([J]a = alla, [N]ej = ingen, nummer, 120-135, !128)? ");
         }
 
-        std::io::stdout().flush().unwrap();
+        let Some(mut input) = las_rad() else {
+            // EOF
+            return Vec::new();
+        };
 
-        let mut input = String::new();
-        if let Ok(size) = std::io::stdin().read_line(&mut input) {
-            if size == 0 {
-                // EOF
-                return Vec::new();
-            }
+        if input == "\n" {
+            return mojliga;
+        }
 
-            if input == "\n" {
-                return mojliga;
-            }
+        input = input.trim().to_lowercase();
+        if input.is_empty() {
+            continue;
+        }
 
-            input = input.trim().to_lowercase();
-            if input.is_empty() {
-                continue;
-            }
+        if input == "j" || input == "y" {
+            return mojliga;
+        }
 
-            if input == "j" || input == "y" {
-                return mojliga;
-            }
+        if input == "n" || input == "q" {
+            return Vec::new();
+        }
 
-            if input == "n" || input == "q" {
-                return Vec::new();
+        let valda = match tolka_val(&input, &mojliga) {
+            Ok(valda) => valda,
+            Err(part) => {
+                println!(
+                    "Ogiltigt val: {}. Rapporter som kan importeras: {}",
+                    part,
+                    formatera_nummer(&mojliga)
+                );
+                continue;
             }
+        };
 
-            let mut valda: Vec<u32> = Vec::new();
-            for part in input.split_whitespace() {
-                if let Ok(n) = part.parse::<u32>()
-                    && mojliga.contains(&n)
-                {
-                    valda.push(n);
-                } else {
-                    println!("Ogiltigt val: {}", part);
-                    valda.clear();
-                    break;
-                }
-            }
+        if valda.is_empty() {
+            println!("Inga rapporter valda.");
+            continue;
+        }
 
-            if !valda.is_empty() {
-                return valda;
-            }
+        print!(
+            "Importera {} ({} st) ([J]a, [N]ej)? ",
+            formatera_nummer(&valda),
+            valda.len()
+        );
+        let Some(svar) = las_rad() else {
+            return Vec::new();
+        };
+        let svar = svar.trim().to_lowercase();
+        if svar.is_empty() || svar == "j" || svar == "y" {
+            return valda;
         }
     }
 }
 
-fn create_journal_entry(rapport: &ZRapport) -> CreateJournal {
-    let title = rapport.verifikatnamn();
-    let date = rapport.datum();
+fn create_journal_entry(
+    rapport: &ZRapport,
+    title: &str,
+    datum: NaiveDate,
+    mapping: &Mapping,
+) -> CreateJournal {
+    let title = title.to_string();
     let mut items: Vec<CreateJournalAccount> =
         Vec::with_capacity(rapport.z_report_transactions.len());
     let zero = Decimal::from(0);
@@ -192,76 +817,195 @@ fn create_journal_entry(rapport: &ZRapport) -> CreateJournal {
         let debit = tr.amount.max(zero);
         let credit = tr.amount.min(zero).abs();
         let account = tr.account_number as i32;
+        let description = mapping.text(tr);
         items.push(CreateJournalAccount {
             account,
             debit,
             credit,
+            description,
         })
     }
 
-    CreateJournal { title, date, items }
+    CreateJournal {
+        title,
+        date: datum,
+        items,
+    }
+}
+
+/// Kontroller som görs innan ett verifikat skapas i Bokio.
+struct Kontroll {
+    /// Bokio-företagets organisationsnummer, `None` om kontrollen är avstängd.
+    bokio_orgnr: Option<Orgnr>,
+    /// Bokio-företagets kontoplan, hämtas en gång per körning.
+    kontoplan: Vec<Account>,
+}
+
+/// Kontrollera att alla konton i verifikatet finns och är aktiva i kontoplanen.
+fn kontrollera_konton(journal: &CreateJournal, kontoplan: &[Account]) -> Result<(), String> {
+    let mut fel: Vec<String> = Vec::new();
+    for item in journal.items.iter() {
+        let konto = kontoplan.iter().find(|a| a.account == item.account);
+        match konto {
+            Some(konto) if konto.is_active => {}
+            Some(konto) => fel.push(format!(
+                "konto {} {} är inte aktivt i kontoplanen",
+                konto.account, konto.name
+            )),
+            None => {
+                // Föreslå aktiva konton i samma kontogrupp
+                let forslag = kontoplan
+                    .iter()
+                    .filter(|a| a.is_active && a.account / 10 == item.account / 10)
+                    .map(|a| format!("{} {}", a.account, a.name))
+                    .collect::<Vec<_>>();
+                if forslag.is_empty() {
+                    fel.push(format!("konto {} saknas i kontoplanen", item.account));
+                } else {
+                    fel.push(format!(
+                        "konto {} saknas i kontoplanen, liknande konto: {}",
+                        item.account,
+                        forslag.join(", ")
+                    ));
+                }
+            }
+        }
+    }
+
+    if fel.is_empty() {
+        Ok(())
+    } else {
+        Err(fel.join("\n"))
+    }
+}
+
+/// Kontrollera att Z-Rapporten tillhör samma företag som Bokio-företaget.
+fn kontrollera_foretag(rapport: &ZRapport, bokio_orgnr: &Orgnr) -> Result<(), String> {
+    let orgnr = rapport.orgnr().map_err(|e| {
+        format!("Z-Rapport {}: {}", rapport.sequence_number, e)
+    })?;
+    if orgnr != *bokio_orgnr {
+        return Err(format!(
+            "Z-Rapport {} tillhör {} men Bokio-företaget har organisationsnummer {}",
+            rapport.sequence_number, orgnr, bokio_orgnr
+        ));
+    }
+    Ok(())
+}
+
+fn spara(filename: &str, data: &[u8]) -> Result<(), String> {
+    std::fs::write(filename, data).map_err(|e| format!("Kunde inte spara {}: {}", filename, e))
 }
 
 fn importera_rapport(
-    easy: &EasyCashier,
-    bokio: &Bokio,
+    source: &dyn ZReportSource,
+    ledger: &dyn LedgerSink,
+    kontroll: &Kontroll,
+    mapping: &Mapping,
     import: &RapportImport,
+    katalog: &str,
+    out: &mut dyn Write,
 ) -> Result<JournalEntry, String> {
-    println!(
+    writeln!(
+        out,
         "Importerar Z-Rapport {}...",
         import.rapport.sequence_number
-    );
+    )
+    .ok();
 
-    print!("* Hämtar PDF... ");
-    std::io::stdout().flush().ok();
-    let (pdf, pdf_filename) = easy.zrapport_pdf(&import.rapport).map_err(|e| {
+    if let Some(bokio_orgnr) = &kontroll.bokio_orgnr {
+        kontrollera_foretag(&import.rapport, bokio_orgnr)?;
+    }
+
+    let datum = import.datum.ok_or_else(|| {
+        format!(
+            "Z-Rapport {} har ogiltig tidpunkt {}",
+            import.rapport.sequence_number, import.rapport.date_created
+        )
+    })?;
+    let journal_entry = create_journal_entry(&import.rapport, &import.titel, datum, mapping);
+    kontrollera_konton(&journal_entry, &kontroll.kontoplan).map_err(|e| {
+        format!(
+            "Kan inte bokföra Z-Rapport {}:\n{}",
+            import.rapport.sequence_number, e
+        )
+    })?;
+
+    write!(out, "* Hämtar PDF... ").ok();
+    out.flush().ok();
+    let (pdf, pdf_filename) = source.document(&import.rapport).map_err(|e| {
         format!(
             "Kunde inte hämta PDF för Z-Rapport {}: {}",
             import.rapport.sequence_number, e
         )
     })?;
-    println!("{}", pdf_filename);
-    std::fs::write(&pdf_filename, pdf).expect("Kunde inte spara PDF.");
+    let pdf_filename = Path::new(katalog).join(pdf_filename).to_string_lossy().to_string();
+    writeln!(out, "{}", pdf_filename).ok();
+    if !katalog.is_empty() {
+        std::fs::create_dir_all(katalog).map_err(|e| format!("{}: {}", katalog, e))?;
+    }
+    spara(&pdf_filename, &pdf)?;
     let json_filename = pdf_filename.replace(".pdf", ".json");
     let json = serde_json::to_vec_pretty(&import.rapport).unwrap();
-    print!("* Sparar {}...", json_filename);
-    std::io::stdout().flush().ok();
-    std::fs::write(&json_filename, json).expect("Kunde inte spara JSON.");
-
-    let journal_entry = create_journal_entry(&import.rapport);
-    let json_filename = pdf_filename.replace(".pdf", "_bokio.json");
-    print!(" {}", json_filename);
-    std::io::stdout().flush().ok();
+    write!(out, "* Sparar {}...", json_filename).ok();
+    out.flush().ok();
+    spara(&json_filename, &json)?;
+
+    let json_filename = pdf_filename.replace(".pdf", &format!("_{}.json", ledger.name().to_lowercase()));
+    write!(out, " {}", json_filename).ok();
+    out.flush().ok();
     let json = serde_json::to_vec_pretty(&journal_entry).unwrap();
-    std::fs::write(&json_filename, json).expect("Kunde inte spara JSON.");
-    println!();
+    spara(&json_filename, &json)?;
+    writeln!(out).ok();
 
-    print!("* Bokför Z-Rapport {}... ", import.rapport.sequence_number);
-    std::io::stdout().flush().ok();
-    let journal_entry = bokio.create_journal_entry(&journal_entry).map_err(|e| {
+    write!(out, "* Bokför Z-Rapport {}... ", import.rapport.sequence_number).ok();
+    out.flush().ok();
+    let journal_entry = ledger.create_entry(&journal_entry).map_err(|e| {
         format!(
             "Kunde inte bokföra verifikat för Z-Rapport {}: {}",
             import.rapport.sequence_number, e
         )
     })?;
-    println!("{}", journal_entry.journal_entry_number);
-
-    print!("* Laddar upp underlag... ");
-    std::io::stdout().flush().ok();
-    bokio
-        .upload(&pdf_filename, "application/pdf", &journal_entry.id)
-        .inspect_err(|e| eprintln!("Misslyckades: {}", e))
-        .inspect(|_| println!("OK"))
-        .ok();
+    writeln!(out, "{}", journal_entry.journal_entry_number).ok();
 
-    println!();
+    write!(out, "* Laddar upp underlag... ").ok();
+    out.flush().ok();
+    match ledger.attach_file(&pdf_filename, "application/pdf", &journal_entry) {
+        Ok(_) => writeln!(out, "OK").ok(),
+        Err(e) => writeln!(out, "Misslyckades: {}", e).ok(),
+    };
+
+    writeln!(out).ok();
     Ok(journal_entry)
 }
 
-fn importera(easy: &EasyCashier, bokio: &Bokio, rapporter: &mut Vec<RapportImport>) {
+fn importera(
+    source: &dyn ZReportSource,
+    ledger: &dyn LedgerSink,
+    kontroll: &Kontroll,
+    mapping: &Mapping,
+    rapporter: &mut [RapportImport],
+    katalog: &str,
+    helskarm: bool,
+) {
+    if helskarm {
+        let forslag = |e: &RapportImport| {
+            e.datum
+                .map(|datum| create_journal_entry(&e.rapport, &e.titel, datum, mapping))
+        };
+        let mut importera = |e: &RapportImport, out: &mut dyn Write| {
+            importera_rapport(source, ledger, kontroll, mapping, e, katalog, out)
+        };
+        tui::valj_och_importera(rapporter, mapping, &forslag, &mut importera)
+            .inspect_err(|err| eprintln!("Terminalfel: {}", err))
+            .ok();
+        lista_rapporter(rapporter, mapping, &Format::Tabell);
+        return;
+    }
+
     loop {
-        lista_rapporter(&rapporter);
-        let valda = valj_rapporter(&rapporter);
+        lista_rapporter(rapporter, mapping, &Format::Tabell);
+        let valda = valj_rapporter(rapporter);
         if valda.is_empty() {
             break;
         }
@@ -272,7 +1016,7 @@ fn importera(easy: &EasyCashier, bokio: &Bokio, rapporter: &mut Vec<RapportImpor
                 .find(|e| e.rapport.sequence_number == seqnr)
                 .unwrap();
             println!();
-            match importera_rapport(&easy, &bokio, imp) {
+            match importera_rapport(source, ledger, kontroll, mapping, imp, katalog, &mut std::io::stdout()) {
                 Ok(journal_entry) => {
                     imp.verifikat.replace(journal_entry);
                 }
@@ -285,19 +1029,227 @@ fn importera(easy: &EasyCashier, bokio: &Bokio, rapporter: &mut Vec<RapportImpor
     }
 }
 
+/// Lista utbetalningarna med Z-Rapporterna de avser och bokför de som
+/// stämmer med kortförsäljningen och inte redan är bokförda.
+fn bokfor_utbetalningar(
+    args: &Cli,
+    source: &dyn ZReportSource,
+    ledger: &dyn LedgerSink,
+    utbetalningar: &[Utbetalning],
+) {
+    let Some(forsta) = utbetalningar.iter().flat_map(|u| u.transaktionsdatum.first()).min() else {
+        println!("Inga utbetalningar i {}", args.settlement);
+        return;
+    };
+    // Rapporter efter midnatt kan höra till dagen före med --cutoff-hour
+    let sista = utbetalningar
+        .iter()
+        .flat_map(|u| u.transaktionsdatum.last())
+        .max()
+        .map(|d| *d + Days::new(1));
+    let zrapporter = source
+        .list_reports(&DateRequest::new(&Some(*forsta), &sista))
+        .inspect_err(|err| {
+            eprintln!("Kunde inte hämta Z-Rapporter: {}", err);
+            std::process::exit(1);
+        })
+        .unwrap();
+
+    // Utbetalningar som täcker samma dagar bokförs tidigast första transaktionsdagen
+    let bokforda = ledger
+        .list_entries(Some(*forsta), None, Some(settlement::TITELPREFIX))
+        .inspect_err(|err| {
+            eprintln!("Kunde inte hämta verifikat från {}: {}", ledger.name(), err);
+            std::process::exit(1);
+        })
+        .unwrap();
+    let bokford = |u: &Utbetalning| {
+        bokforda
+            .iter()
+            .find(|e| e.title == u.titel() && e.reversed_by_journal_entry_id.is_none())
+    };
+    let fiscal_years = ledger
+        .fiscal_years()
+        .inspect_err(|err| {
+            eprintln!("Kunde inte hämta räkenskapsår från {}: {}", ledger.name(), err);
+            std::process::exit(1);
+        })
+        .unwrap();
+
+    println!(
+        "| DATUM      | {:<30} |   BRUTTO |   AVGIFT |    NETTO | KORT I Z | DIFFERENS | Z-NUMMER",
+        "UTBETALNING"
+    );
+    println!("|------------|-{:-<30}-|----------|----------|----------|----------|-----------|---------", "-");
+    let utbetalning = |u: &Utbetalning| {
+        format!(
+            "| {} | {:<30} | {:>8.2} | {:>8.2} | {:>8.2} |",
+            format_local_date(&u.datum),
+            format!("{} {}", u.inlosare, u.referens),
+            u.brutto,
+            u.avgift,
+            u.netto
+        )
+    };
+    let (bokforda_utbetalningar, obokforda) = utbetalningar.iter().partition::<Vec<_>, _>(|u| bokford(u).is_some());
+    for u in bokforda_utbetalningar {
+        let verifikat = bokford(u).unwrap();
+        println!("{}          |           |", utbetalning(u));
+        println!("    bokförd som {} {}", verifikat.journal_entry_number, verifikat.date);
+    }
+
+    // Bruttobelopp som redan bokförts mot Z-Rapporterna räknas bort från
+    // kortförsäljningen, raderna behövs för att se vilka rapporter de avser
+    let tackningar = bokforda
+        .iter()
+        .map(|e| ledger.entry(e))
+        .collect::<Result<Vec<_>, _>>()
+        .map(|bokforda| settlement::tackningar(&bokforda))
+        .inspect_err(|err| {
+            eprintln!("Kunde inte hämta verifikat från {}: {}", ledger.name(), err);
+            std::process::exit(1);
+        })
+        .unwrap();
+    let mut bokforbara = Vec::new();
+    for koppling in settlement::koppla(&obokforda, &zrapporter, &tackningar, args.brytpunkt) {
+        let znummer = koppling
+            .rapporter
+            .iter()
+            .map(|r| r.sequence_number.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        // Utbetalningar som delar dagar jämförs tillsammans mot Z-Rapporterna
+        for (i, u) in koppling.utbetalningar.iter().enumerate() {
+            if i + 1 < koppling.utbetalningar.len() {
+                println!("{}          |           |", utbetalning(u));
+            } else {
+                println!(
+                    "{} {:>8.2} | {:>9.2} | {}",
+                    utbetalning(u),
+                    koppling.kvar(),
+                    koppling.differens(),
+                    znummer
+                );
+            }
+        }
+        let stangd = koppling
+            .utbetalningar
+            .iter()
+            .find_map(|u| kontrollera_period(u.datum, &fiscal_years));
+        if koppling.rapporter.is_empty() {
+            let dagar = koppling
+                .utbetalningar
+                .iter()
+                .flat_map(|u| u.transaktionsdatum.iter())
+                .collect::<std::collections::BTreeSet<_>>();
+            println!("    hittade inga Z-Rapporter för {:?}", dagar);
+        } else if !koppling.differens().is_zero() {
+            println!("    bruttobeloppet stämmer inte med kortförsäljningen i Z-Rapporterna");
+            if !koppling.bokfort.is_zero() {
+                println!(
+                    "    {:.2} av {:.2} är redan bokfört mot rapporterna",
+                    koppling.bokfort, koppling.kort
+                );
+            }
+        } else if let Some(stangd) = stangd {
+            println!("    kan inte bokföras: {}", stangd);
+        } else {
+            for u in koppling.utbetalningar.iter() {
+                bokforbara.push(settlement::create_journal_entry(
+                    u,
+                    &koppling,
+                    args.bankkonto,
+                    args.avgiftskonto,
+                ));
+            }
+        }
+    }
+
+    println!();
+    if bokforbara.is_empty() {
+        println!("Inga utbetalningar att bokföra");
+        return;
+    }
+    print!("Bokför {} utbetalningar ([J]a, [N]ej)? ", bokforbara.len());
+    let Some(svar) = las_rad() else {
+        return;
+    };
+    let svar = svar.trim().to_lowercase();
+    if !svar.is_empty() && svar != "j" && svar != "ja" {
+        return;
+    }
+
+    let kontoplan = ledger
+        .chart_of_accounts()
+        .inspect_err(|err| {
+            eprintln!("{}: kunde inte hämta kontoplan: {}", ledger.name(), err);
+            std::process::exit(1);
+        })
+        .unwrap();
+    for journal_entry in bokforbara {
+        if let Err(err) = kontrollera_konton(&journal_entry, &kontoplan) {
+            eprintln!("Kan inte bokföra {}:\n{}", journal_entry.title, err);
+            std::process::exit(1);
+        }
+        print!("* Bokför {}... ", journal_entry.title);
+        std::io::stdout().flush().ok();
+        match ledger.create_entry(&journal_entry) {
+            Ok(entry) => println!("{}", entry.journal_entry_number),
+            Err(err) => {
+                println!();
+                eprintln!("Kunde inte bokföra {}: {}", journal_entry.title, err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
 fn main() {
     let mut args = Cli {
+        kommando: Kommando::Importera,
         orgnummer: utils::get_env("EASYCASHIER_COMPANY"),
         start_date: None,
         end_date: None,
+        reject_personnummer: false,
+        allow_company_mismatch: false,
+        brytpunkt: 0,
+        strict_schema: false,
+        schema_baseline: String::new(),
+        update_baseline: false,
+        mapping: utils::get_env("ECBOKIO_MAPPING"),
+        bokio_page_size: 100,
+        report: None,
+        from_seq: None,
+        to_seq: None,
+        kassa: None,
+        no_tui: false,
+        journal_cache: String::new(),
+        output_dir: utils::get_env("ECBOKIO_OUTPUT_DIR"),
+        refresh: false,
+        format: Format::Tabell,
+        record: String::new(),
+        replay: String::new(),
+        settlement: String::new(),
+        bankkonto: settlement::BANKKONTO,
+        avgiftskonto: settlement::AVGIFTSKONTO,
+        title_template: utils::get_env_or_default("ECBOKIO_TITLE_TEMPLATE", LEGACY_TITLE_TEMPLATE),
         easycashier_url: utils::get_env_or_default("EASYCASHIER_URL", EASYCASHIER_URL),
         easycashier_username: utils::get_env("EASYCASHIER_USERNAME"),
         easycashier_password: utils::get_env("EASYCASHIER_PASSWORD"),
         bokio_api_url: utils::get_env_or_default("BOKIO_API_URL", BOKIO_API_URL),
         bokio_api_token: utils::get_env("BOKIO_API_TOKEN"),
         bokio_company_id: utils::get_env("BOKIO_COMPANY_ID"),
+        ledger: Bokforing::Bokio,
+        fortnox_api_url: utils::get_env_or_default("FORTNOX_API_URL", FORTNOX_API_URL),
+        fortnox_access_token: utils::get_env("FORTNOX_ACCESS_TOKEN"),
+        fortnox_voucher_series: utils::get_env_or_default("FORTNOX_VOUCHER_SERIES", "A"),
     };
 
+    let ledger = utils::get_env("ECBOKIO_LEDGER");
+    if !ledger.is_empty() {
+        args.ledger = parse_ledger(&ledger);
+    }
+
     let mut iter = std::env::args().skip(1);
     while let Some(arg) = iter.next() {
         if let Some(url) = check_arg("easycashier-url", &arg, &mut iter) {
@@ -321,6 +1273,118 @@ fn main() {
             args.bokio_api_token = token;
         } else if let Some(company_id) = check_arg("bokio-company-id", &arg, &mut iter) {
             args.bokio_company_id = company_id;
+        } else if let Some(ledger) = check_arg("ledger", &arg, &mut iter) {
+            args.ledger = parse_ledger(&ledger);
+        } else if let Some(url) = check_arg("fortnox-api-url", &arg, &mut iter) {
+            args.fortnox_api_url = url;
+        } else if let Some(token) = check_arg("fortnox-access-token", &arg, &mut iter) {
+            args.fortnox_access_token = token;
+        } else if let Some(series) = check_arg("fortnox-voucher-series", &arg, &mut iter) {
+            args.fortnox_voucher_series = series;
+        } else if let Some(size) = check_arg("bokio-page-size", &arg, &mut iter) {
+            args.bokio_page_size = size
+                .parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .unwrap_or_else(|| panic!("--bokio-page-size expected a positive number"));
+        } else if let Some(id) = check_arg("report", &arg, &mut iter) {
+            args.report = id
+                .parse::<ZRapportId>()
+                .inspect_err(|err| {
+                    eprintln!("--report: {}", err);
+                    std::process::exit(1);
+                })
+                .ok();
+        } else if let Some(seq) = check_arg("from-seq", &arg, &mut iter) {
+            args.from_seq = Some(
+                seq.parse::<u32>()
+                    .unwrap_or_else(|_| panic!("--from-seq expected number")),
+            );
+        } else if let Some(seq) = check_arg("to-seq", &arg, &mut iter) {
+            args.to_seq = Some(
+                seq.parse::<u32>()
+                    .unwrap_or_else(|_| panic!("--to-seq expected number")),
+            );
+        } else if let Some(kassa) = check_arg("cash-register", &arg, &mut iter) {
+            args.kassa = sequence::parse_kassa(&kassa)
+                .inspect_err(|err| {
+                    eprintln!("--cash-register: {}", err);
+                    std::process::exit(1);
+                })
+                .ok();
+        } else if let Some(filename) = check_arg("journal-cache", &arg, &mut iter) {
+            args.journal_cache = filename;
+        } else if let Some(dir) = check_arg("output-dir", &arg, &mut iter) {
+            args.output_dir = dir;
+        } else if let Some(format) = check_arg("format", &arg, &mut iter) {
+            args.format = format
+                .parse::<Format>()
+                .inspect_err(|err| {
+                    eprintln!("--format: {}", err);
+                    std::process::exit(1);
+                })
+                .unwrap();
+        } else if let Some(dir) = check_arg("record", &arg, &mut iter) {
+            args.record = dir;
+        } else if let Some(dir) = check_arg("replay", &arg, &mut iter) {
+            args.replay = dir;
+        } else if check_flag("no-tui", &arg) {
+            args.no_tui = true;
+        } else if check_flag("refresh", &arg) {
+            args.refresh = true;
+        } else if let Some(hour) = check_arg("cutoff-hour", &arg, &mut iter) {
+            args.brytpunkt = hour
+                .parse::<u32>()
+                .ok()
+                .filter(|h| *h < 24)
+                .unwrap_or_else(|| panic!("--cutoff-hour expected 0-23"));
+        } else if check_flag("reject-personnummer", &arg) {
+            args.reject_personnummer = true;
+        } else if check_flag("allow-company-mismatch", &arg) {
+            args.allow_company_mismatch = true;
+        } else if check_flag("strict-schema", &arg) {
+            args.strict_schema = true;
+        } else if let Some(filename) = check_arg("schema-baseline", &arg, &mut iter) {
+            args.schema_baseline = filename;
+        } else if check_flag("update-baseline", &arg) {
+            args.update_baseline = true;
+        } else if let Some(filename) = check_arg("mapping", &arg, &mut iter) {
+            args.mapping = filename;
+        } else if let Some(template) = check_arg("title-template", &arg, &mut iter) {
+            args.title_template = template;
+        } else if arg == "import" {
+            args.kommando = Kommando::Importera;
+        } else if arg == "schema-check" {
+            args.kommando = Kommando::SchemaCheck;
+        } else if arg == "audit-sequence" {
+            args.kommando = Kommando::AuditSequence;
+        } else if arg == "settlement" {
+            args.kommando = Kommando::Utbetalningar;
+            args.settlement = iter.next().unwrap_or_else(|| panic!("settlement expected FILE"));
+        } else if let Some(account) = check_arg("bank-account", &arg, &mut iter) {
+            args.bankkonto = account
+                .parse::<u16>()
+                .unwrap_or_else(|_| panic!("--bank-account expected account number"));
+        } else if let Some(account) = check_arg("fee-account", &arg, &mut iter) {
+            args.avgiftskonto = account
+                .parse::<u16>()
+                .unwrap_or_else(|_| panic!("--fee-account expected account number"));
+        } else if arg == "show" {
+            args.kommando = Kommando::Visa;
+            let nr = iter.next().unwrap_or_else(|| panic!("show expected SEQ or STORE:REG:SEQ"));
+            if nr.contains(':') {
+                args.report = nr
+                    .parse::<ZRapportId>()
+                    .inspect_err(|err| {
+                        eprintln!("show: {}", err);
+                        std::process::exit(1);
+                    })
+                    .ok();
+            } else {
+                let nr = nr.parse::<u32>().unwrap_or_else(|_| panic!("show expected number"));
+                args.from_seq = Some(nr);
+                args.to_seq = Some(nr);
+            }
         } else {
             eprintln!("{}: invalid option", arg);
             std::process::exit(1);
@@ -328,9 +1392,64 @@ fn main() {
     }
 
     if !args.orgnummer.is_empty() {
-        args.orgnummer = format_orgnr(&args.orgnummer);
+        args.orgnummer = parse_orgnr(&args, &args.orgnummer).to_string();
     }
 
+    let mapping = if args.mapping.is_empty() {
+        Mapping::default()
+    } else {
+        Mapping::load(&args.mapping)
+            .inspect_err(|err| {
+                eprintln!("Kunde inte läsa kontomappning: {}", err);
+                std::process::exit(1);
+            })
+            .unwrap()
+    };
+
+    let utbetalningar = if args.kommando == Kommando::Utbetalningar {
+        settlement::las(&args.settlement)
+            .inspect_err(|err| {
+                eprintln!("Kunde inte läsa avräkningsfil: {}", err);
+                std::process::exit(1);
+            })
+            .unwrap()
+    } else {
+        Vec::new()
+    };
+
+    let template = TitleTemplate::new(&args.title_template)
+        .inspect_err(|err| {
+            eprintln!("Ogiltig mall för verifikatnamn: {}", err);
+            std::process::exit(1);
+        })
+        .unwrap();
+
+    let agent = if !args.replay.is_empty() {
+        // Inloggningsuppgifter är maskerade i inspelningen och behövs inte
+        for value in [
+            &mut args.easycashier_username,
+            &mut args.easycashier_password,
+            &mut args.bokio_api_token,
+            &mut args.bokio_company_id,
+            &mut args.fortnox_access_token,
+        ] {
+            if value.is_empty() {
+                *value = recording::REDACTED.to_string();
+            }
+        }
+        recording::agent_spela_upp(&args.replay)
+    } else if !args.record.is_empty() {
+        recording::agent_spela_in(&args.record)
+    } else {
+        Ok(recording::agent())
+    };
+    let agent = agent
+        .inspect_err(|err| {
+            eprintln!("Inspelning: {}", err);
+            std::process::exit(1);
+        })
+        .unwrap();
+
     if args.easycashier_username.is_empty() {
         let username = read_prompt_trim("EasyCashier username: ");
         if username.is_empty() {
@@ -347,23 +1466,8 @@ fn main() {
         args.easycashier_password = password;
     }
 
-    if args.bokio_api_token.is_empty() {
-        let token = read_password_trim("Bokio API token: ");
-        if token.is_empty() {
-            return;
-        }
-        args.bokio_api_token = token;
-    }
-
-    if args.bokio_company_id.is_empty() {
-        let company_id = read_prompt_trim("Bokio company id: ");
-        if company_id.is_empty() {
-            return;
-        }
-        args.bokio_company_id = company_id;
-    }
-
     let easy = EasyCashier::login(
+        agent.clone(),
         &args.easycashier_url,
         &args.easycashier_username,
         &args.easycashier_password,
@@ -381,35 +1485,250 @@ fn main() {
         if orgnr.is_empty() {
             return
         }
-        easy.company = format_orgnr(&orgnr);
+        easy.company = orgnr;
     }
 
-    let bokio = Bokio::new(
-        &args.bokio_api_url,
-        &args.bokio_company_id,
-        &args.bokio_api_token,
-    );
+    let company = parse_orgnr(&args, &easy.company);
+    easy.company = company.to_string();
+
+    if args.kommando == Kommando::SchemaCheck {
+        schema_check(&args, &easy);
+        return;
+    }
 
-    let (mut rapporter, dates) = hamta_rapporter(&args, &easy, &bokio)
+    let ledger: Box<dyn LedgerSink> = match args.ledger {
+        Bokforing::Bokio => {
+            if args.bokio_api_token.is_empty() {
+                let token = read_password_trim("Bokio API token: ");
+                if token.is_empty() {
+                    return;
+                }
+                args.bokio_api_token = token;
+            }
+
+            if args.bokio_company_id.is_empty() {
+                let company_id = read_prompt_trim("Bokio company id: ");
+                if company_id.is_empty() {
+                    return;
+                }
+                args.bokio_company_id = company_id;
+            }
+
+            let mut bokio = Bokio::new(
+                &args.bokio_api_url,
+                &args.bokio_company_id,
+                &args.bokio_api_token,
+            );
+            bokio.page_size = args.bokio_page_size;
+            bokio.agent = agent;
+            Box::new(bokio)
+        }
+        Bokforing::Fortnox => {
+            if args.fortnox_access_token.is_empty() {
+                let token = read_password_trim("Fortnox access token: ");
+                if token.is_empty() {
+                    return;
+                }
+                args.fortnox_access_token = token;
+            }
+
+            let mut fortnox = Fortnox::new(&args.fortnox_api_url, &args.fortnox_access_token);
+            fortnox.voucher_series = args.fortnox_voucher_series.clone();
+            fortnox.agent = agent;
+            Box::new(fortnox)
+        }
+    };
+
+    let source: &dyn ZReportSource = &easy;
+    let ledger = ledger.as_ref();
+
+    let bokio_orgnr = ledger
+        .organization_number()
+        .map_err(|err| format!("{}: kunde inte hämta företagsuppgifter: {}", ledger.name(), err))
+        .and_then(|orgnr| {
+            Orgnr::parse(&orgnr, true).map_err(|err| format!("{}: {}", ledger.name(), err))
+        })
+        .inspect_err(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+        .unwrap();
+
+    if bokio_orgnr != company {
+        eprintln!(
+            "EasyCashier-företaget {} matchar inte {}-företaget {}",
+            company,
+            ledger.name(),
+            bokio_orgnr
+        );
+        if !args.allow_company_mismatch {
+            eprintln!("Använd --allow-company-mismatch för att importera ändå.");
+            std::process::exit(1);
+        }
+    }
+
+    if args.kommando == Kommando::Utbetalningar {
+        bokfor_utbetalningar(&args, source, ledger, &utbetalningar);
+        return;
+    }
+
+    let zrapporter = match &args.report {
+        _ if args.strict_schema => hamta_med_schemakontroll(&args, &easy),
+        Some(id) => source.report(id).map(|rapport| vec![rapport]),
+        None => source.list_reports(&datumintervall(&args)),
+    };
+    let zrapporter = zrapporter
         .inspect_err(|err| {
             eprintln!("Kunde inte hämta Z-Rapporter: {}", err);
             std::process::exit(1);
         })
         .unwrap();
+    let zrapporter = filtrera_rapporter(&args, zrapporter)
+        .inspect_err(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        })
+        .unwrap();
+
+    // En enskild rapport gäller bara rapportens datum
+    let dates = match zrapporter.first().filter(|_| args.report.is_some()) {
+        Some(rapport) => {
+            let datum = rapport.datum(args.brytpunkt).ok();
+            DateRequest::new(&datum.or(args.start_date), &datum.or(args.end_date))
+        }
+        None => datumintervall(&args),
+    };
+    // Cachen gäller ett företag i ett bokföringssystem
+    let ledger_id = format!("{}_{}", ledger.name().to_lowercase(), bokio_orgnr);
+    let cache_filename = if !args.journal_cache.is_empty() {
+        args.journal_cache.clone()
+    } else if !args.replay.is_empty() {
+        // Uppspelningen ska inte läsa eller skriva över den riktiga cachen
+        Path::new(&args.replay)
+            .join(format!("ecbokio_journal_{}.json", ledger_id))
+            .to_string_lossy()
+            .to_string()
+    } else {
+        format!("ecbokio_journal_{}.json", ledger_id)
+    };
+    let mut cache = JournalCache::sync(
+        &cache_filename,
+        ledger,
+        &ledger_id,
+        &titelprefix(&template),
+        bokio_startdatum(&dates),
+        args.refresh,
+    )
+    .inspect_err(|err| {
+        eprintln!("Kunde inte hämta verifikat från {}: {}", ledger.name(), err);
+        std::process::exit(1);
+    })
+    .unwrap();
+    cache
+        .save(&cache_filename)
+        .inspect_err(|err| eprintln!("Varning: kunde inte spara cache: {}", err))
+        .ok();
+
+    if args.kommando == Kommando::AuditSequence {
+        granska_nummerserie(&dates, &template, &cache, &zrapporter);
+        return;
+    }
+
+    let mut rapporter =
+        hamta_rapporter(args.brytpunkt, &dates, &template, &company, ledger, &cache, zrapporter)
+            .inspect_err(|err| {
+                eprintln!("Kunde inte hämta räkenskapsår från {}: {}", ledger.name(), err);
+                std::process::exit(1);
+            })
+            .unwrap();
+
+    if args.kommando == Kommando::Visa {
+        if rapporter.is_empty() {
+            eprintln!("Hittade ingen Z-Rapport, ange datum med --start eller --date");
+            std::process::exit(1);
+        }
+        let kontoplan = ledger
+            .chart_of_accounts()
+            .inspect_err(|err| {
+                eprintln!("{}: kunde inte hämta kontoplan: {}", ledger.name(), err);
+                std::process::exit(1);
+            })
+            .unwrap();
+        for (i, e) in rapporter.iter_mut().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            // Raderna i bokförda verifikat behövs för att visa skillnaderna
+            if let Some(verifikat) = &e.verifikat {
+                e.verifikat = Some(
+                    ledger
+                        .entry(verifikat)
+                        .inspect_err(|err| {
+                            eprintln!("Kunde inte hämta verifikat {} från {}: {}", verifikat.id, ledger.name(), err);
+                            std::process::exit(1);
+                        })
+                        .unwrap(),
+                );
+            }
+            show::visa_rapport(e, &mapping, &kontoplan);
+        }
+        return;
+    }
+
+    if args.format != Format::Tabell {
+        lista_rapporter(&rapporter, &mapping, &args.format);
+        return;
+    }
 
     println!(
         "{} Z-Rapporter för {} ({} - {})",
         rapporter.len(),
-        easy.company,
+        source.company(),
         format_local_date(&dates.start_date),
         format_local_date(&dates.end_date),
     );
 
+    let luckor = sequence::luckor(
+        rapporter.iter().map(|e| &e.rapport),
+        args.from_seq,
+        args.to_seq,
+    );
+    if !luckor.is_empty() {
+        eprintln!();
+        eprintln!("!!! VARNING: Z-nummer saknas i kassaregistrets nummerserie !!!");
+        for lucka in luckor.iter() {
+            eprintln!("!!!   {}", lucka);
+        }
+        eprintln!();
+    }
+
     if !rapporter.is_empty() {
         let antal_skippade = rakna_importerade_rapporter(&rapporter);
-        importera(&easy, &bokio, &mut rapporter);
+        let kontoplan = ledger
+            .chart_of_accounts()
+            .inspect_err(|err| {
+                eprintln!("{}: kunde inte hämta kontoplan: {}", ledger.name(), err);
+                std::process::exit(1);
+            })
+            .unwrap();
+        let kontroll = Kontroll {
+            bokio_orgnr: Some(bokio_orgnr).filter(|_| !args.allow_company_mismatch),
+            kontoplan,
+        };
+        let helskarm = !args.no_tui && tui::tillganglig();
+        importera(source, ledger, &kontroll, &mapping, &mut rapporter, &args.output_dir, helskarm);
         let antal_importerade = rakna_importerade_rapporter(&rapporter) - antal_skippade;
 
+        if antal_importerade > 0 {
+            for verifikat in rapporter.iter().filter_map(|e| e.verifikat.clone()) {
+                cache.upsert(verifikat);
+            }
+            cache
+                .save(&cache_filename)
+                .inspect_err(|err| eprintln!("Varning: kunde inte spara cache: {}", err))
+                .ok();
+        }
+
         println!();
         println!("{} Z-Rapporter importerades", antal_importerade);
         if antal_skippade > 0 {
@@ -0,0 +1,129 @@
+use crate::zrapport::{PaymentMethod, TransactionType, ZRapport, ZRapportTrans};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Inställningar för ett konto i Z-Rapporten.
+#[derive(Debug, Default, Deserialize)]
+pub struct AccountMapping {
+    /// Text på verifikatraden, ersätter text från EasyCashier.
+    pub text: Option<String>,
+}
+
+/// Kolumn i listningen av Z-Rapporter med summan av ett eller flera konton.
+#[derive(Debug, Deserialize)]
+pub struct Column {
+    pub label: String,
+    pub accounts: Vec<u16>,
+}
+
+/// Rubrik för betalkonton som inte finns i någon kolumn.
+pub const OVRIGT: &str = "ÖVRIGT";
+/// Rubrik för försäljning exklusive moms.
+pub const FORSALJNING: &str = "FÖRSÄLJNING";
+
+fn default_columns() -> Vec<Column> {
+    [("KORT", 1580), ("KONTANT", 1911), ("SWISH", 1932)]
+        .into_iter()
+        .map(|(label, account)| Column {
+            label: label.to_string(),
+            accounts: vec![account],
+        })
+        .collect()
+}
+
+/// Kontomappning som läses från fil (`--mapping`), t.ex.
+///
+/// ```json
+/// {
+///   "accounts": { "1580": { "text": "Kort" }, "3001": { "text": "Försäljning 25%" } },
+///   "columns": [ { "label": "KORT", "accounts": [1580] }, { "label": "PRESENTKORT", "accounts": [2421] } ]
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct Mapping {
+    #[serde(default)]
+    pub accounts: HashMap<u16, AccountMapping>,
+    /// Kolumner i listningen, standard är kort, kontant och Swish.
+    #[serde(default = "default_columns")]
+    pub columns: Vec<Column>,
+}
+
+impl Default for Mapping {
+    fn default() -> Self {
+        Mapping {
+            accounts: HashMap::new(),
+            columns: default_columns(),
+        }
+    }
+}
+
+impl Mapping {
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let json = std::fs::read(filename).map_err(|e| format!("{}: {}", filename, e))?;
+        serde_json::from_slice(&json).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    /// Text för en verifikatrad. Mappningen har företräde, annars härleds
+    /// texten från transaktionen i EasyCashier.
+    pub fn text(&self, tr: &ZRapportTrans) -> Option<String> {
+        self.accounts
+            .get(&tr.account_number)
+            .and_then(|a| a.text.clone())
+            .or_else(|| transaktionstext(tr))
+    }
+
+    /// Rubriker i listningen: konfigurerade kolumner, ÖVRIGT och FÖRSÄLJNING.
+    pub fn rubriker(&self) -> Vec<&str> {
+        self.columns
+            .iter()
+            .map(|c| c.label.as_str())
+            .chain([OVRIGT, FORSALJNING])
+            .collect()
+    }
+
+    /// Belopp i samma ordning som `rubriker`. ÖVRIGT är summan av betalkonton
+    /// (kontoklass 1) som inte finns i någon kolumn, t.ex. presentkort.
+    pub fn belopp(&self, rapport: &ZRapport) -> Vec<Decimal> {
+        let listade = |account: u16| self.columns.iter().any(|c| c.accounts.contains(&account));
+        let ovrigt = rapport
+            .z_report_transactions
+            .iter()
+            .filter(|tr| (1000..2000).contains(&tr.account_number) && !listade(tr.account_number))
+            .map(|tr| tr.amount)
+            .sum();
+
+        self.columns
+            .iter()
+            .map(|c| c.accounts.iter().map(|a| rapport.konto(*a)).sum())
+            .chain([ovrigt, rapport.forsaljning()])
+            .collect()
+    }
+}
+
+fn transaktionstext(tr: &ZRapportTrans) -> Option<String> {
+    if let Some(description) = tr.description.as_ref().filter(|d| !d.trim().is_empty()) {
+        return Some(description.trim().to_string());
+    }
+
+    if let Some(method) = &tr.payment_method {
+        let text = match method {
+            PaymentMethod::Cash => "Kontant",
+            PaymentMethod::Card => "Kort",
+            PaymentMethod::Swish => "Swish",
+            PaymentMethod::GiftCard => "Presentkort",
+            PaymentMethod::Invoice => "Faktura",
+            PaymentMethod::Other(s) => s,
+        };
+        return Some(text.to_string());
+    }
+
+    let moms = tr.vat_rate.map(|r| format!(" {}%", r.normalize()));
+    match &tr.transaction_type {
+        Some(TransactionType::Sale) => Some(format!("Försäljning{}", moms.unwrap_or_default())),
+        Some(TransactionType::Return) => Some(format!("Retur{}", moms.unwrap_or_default())),
+        Some(TransactionType::Vat) => Some(format!("Utgående moms{}", moms.unwrap_or_default())),
+        Some(TransactionType::Rounding) => Some("Öresavrundning".to_string()),
+        _ => None,
+    }
+}
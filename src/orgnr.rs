@@ -0,0 +1,137 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Svenskt organisationsnummer (eller personnummer för enskild firma).
+///
+/// Accepterar formerna `NNNNNNNNNN`, `NNNNNN-NNNN`, `SSNNNNNNNNNN` och
+/// `SSNNNNNN-NNNN` där `SS` är sekelprefix (`16` för juridiska personer,
+/// `19` eller `20` för personnummer). Kontrollsiffran verifieras med Luhn.
+#[derive(Debug, Clone, Copy)]
+pub struct Orgnr {
+    century: Option<u8>,
+    digits: [u8; 10],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrgnrError {
+    Format(String),
+    Century(String),
+    Checksum(String),
+    Personnummer(String),
+}
+
+impl Display for OrgnrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrgnrError::Format(s) => write!(f, "{}: ogiltigt format på organisationsnummer", s),
+            OrgnrError::Century(s) => write!(f, "{}: ogiltigt sekelprefix", s),
+            OrgnrError::Checksum(s) => write!(f, "{}: felaktig kontrollsiffra", s),
+            OrgnrError::Personnummer(s) => {
+                write!(f, "{}: personnummer är inte tillåtet som organisationsnummer", s)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OrgnrError {}
+
+fn luhn(digits: &[u8]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let d = *d as u32 * if i.is_multiple_of(2) { 2 } else { 1 };
+            if d > 9 { d - 9 } else { d }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+impl Orgnr {
+    /// Tolka och validera ett organisationsnummer. Om `allow_personnummer` är
+    /// `false` avvisas nummer med personnummerform (enskild firma).
+    pub fn parse(s: &str, allow_personnummer: bool) -> Result<Orgnr, OrgnrError> {
+        let orgnr = s.parse::<Orgnr>()?;
+        if !allow_personnummer && orgnr.is_personnummer() {
+            return Err(OrgnrError::Personnummer(s.trim().to_string()));
+        }
+        Ok(orgnr)
+    }
+
+    /// Nummer där tredje siffran är 0 eller 1 har formen av ett personnummer
+    /// (månad 01-12) och tillhör en enskild näringsidkare.
+    pub fn is_personnummer(&self) -> bool {
+        self.digits[2] < 2
+    }
+}
+
+impl FromStr for Orgnr {
+    type Err = OrgnrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let (head, tail) = match trimmed.split_once(['-', '+']) {
+            Some((head, tail)) if tail.len() == 4 => (head, tail),
+            Some(_) => return Err(OrgnrError::Format(trimmed.to_string())),
+            None if trimmed.len() >= 4 => trimmed.split_at(trimmed.len() - 4),
+            None => return Err(OrgnrError::Format(trimmed.to_string())),
+        };
+
+        if !head.chars().chain(tail.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(OrgnrError::Format(trimmed.to_string()));
+        }
+
+        let all = head
+            .bytes()
+            .chain(tail.bytes())
+            .map(|b| b - b'0')
+            .collect::<Vec<_>>();
+        let (century, rest) = match all.len() {
+            10 => (None, &all[..]),
+            12 => (Some(all[0] * 10 + all[1]), &all[2..]),
+            _ => return Err(OrgnrError::Format(trimmed.to_string())),
+        };
+
+        let mut digits = [0u8; 10];
+        digits.copy_from_slice(rest);
+        let orgnr = Orgnr { century, digits };
+        match century {
+            None => {}
+            Some(16) if !orgnr.is_personnummer() => {}
+            Some(19 | 20) if orgnr.is_personnummer() => {}
+            Some(_) => return Err(OrgnrError::Century(trimmed.to_string())),
+        }
+
+        if !luhn(&digits) {
+            return Err(OrgnrError::Checksum(trimmed.to_string()));
+        }
+
+        Ok(orgnr)
+    }
+}
+
+/// Sekelprefixet ingår inte i jämförelsen, `16556677-8899` och
+/// `556677-8899` är samma organisationsnummer.
+impl PartialEq for Orgnr {
+    fn eq(&self, other: &Self) -> bool {
+        self.digits == other.digits
+    }
+}
+
+impl Eq for Orgnr {}
+
+impl Display for Orgnr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(century) = self.century {
+            write!(f, "{:02}", century)?;
+        }
+        for d in &self.digits[0..6] {
+            write!(f, "{}", d)?;
+        }
+        write!(f, "-")?;
+        for d in &self.digits[6..10] {
+            write!(f, "{}", d)?;
+        }
+        Ok(())
+    }
+}
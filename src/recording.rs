@@ -0,0 +1,375 @@
+//! Inspelning och uppspelning av HTTP-trafik mot EasyCashier och Bokio.
+//!
+//! Med `--record KATALOG` sparas varje anrop som en JSON-fil (`0001.json`,
+//! `0002.json`, ...) med anrop och svar. Lösenord, tokens och
+//! autentiseringshuvuden ersätts med `REDACTED` innan filen skrivs, både i
+//! JSON, i URL-parametrar och i textinnehåll. Svar som inte är text,
+//! t.ex. PDF, sparas i en separat fil.
+//!
+//! Med `--replay KATALOG` besvaras anropen från inspelningen i stället för
+//! från servern. Anrop matchas på metod, sökväg och parametrar i den ordning
+//! de spelades in, så samma inspelning kan användas mot en annan bas-URL.
+//! En del av sökvägen eller ett parametervärde som är `REDACTED` i anropet
+//! eller inspelningen matchar vilket värde som helst.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use ureq::http::{Request, Response, Uri};
+use ureq::middleware::{Middleware, MiddlewareNext};
+use ureq::{Agent, AsSendBody, Body, Error, SendBody};
+
+pub const REDACTED: &str = "REDACTED";
+
+/// Huvuden som aldrig sparas i klartext.
+const KANSLIGA_HUVUDEN: [&str; 4] = ["authorization", "x-auth-token", "cookie", "set-cookie"];
+
+/// JSON-nycklar som aldrig sparas i klartext (jämförs utan skiftläge).
+const KANSLIGA_NYCKLAR: [&str; 3] = ["password", "token", "username"];
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Innehall {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    json: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    /// Fil i inspelningskatalogen med binärt innehåll.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_file: Option<String>,
+    /// Storlek på innehåll som inte sparats, t.ex. uppladdade filer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_length: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Anrop {
+    method: String,
+    url: String,
+    #[serde(flatten)]
+    innehall: Innehall,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Svar {
+    status: u16,
+    #[serde(flatten)]
+    innehall: Innehall,
+}
+
+/// Ett inspelat anrop med svar.
+#[derive(Debug, Deserialize, Serialize)]
+struct Post {
+    request: Anrop,
+    response: Svar,
+}
+
+/// Ersätt värden för känsliga nycklar, rekursivt.
+fn maskera(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if kanslig_nyckel(key) && !value.is_null() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    maskera(value);
+                }
+            }
+        }
+        Value::Array(lst) => lst.iter_mut().for_each(maskera),
+        _ => {}
+    }
+}
+
+fn kanslig_nyckel(key: &str) -> bool {
+    let key = key.trim().to_ascii_lowercase();
+    KANSLIGA_NYCKLAR.iter().any(|k| key.contains(k))
+}
+
+/// Ersätt värden för känsliga nycklar i `nyckel=värde`-par åtskilda med `&`.
+fn maskera_par(text: &str) -> String {
+    text.split('&')
+        .map(|par| match par.split_once('=') {
+            Some((key, value)) if kanslig_nyckel(key) && !value.is_empty() => format!("{}={}", key, REDACTED),
+            _ => par.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Ersätt känsliga parametervärden i URL:en.
+fn maskera_url(url: &str) -> String {
+    match url.split_once('?') {
+        Some((path, query)) => format!("{}?{}", path, maskera_par(query)),
+        None => url.to_string(),
+    }
+}
+
+/// Ersätt värden för känsliga nycklar i text, både formulärdata
+/// (`password=...&...`) och rader som `Token: ...`.
+fn maskera_text(text: &str) -> String {
+    text.split('\n')
+        .map(|rad| match rad.split_once(':') {
+            Some((key, value)) if kanslig_nyckel(key) && !value.trim().is_empty() => {
+                format!("{}: {}", key, REDACTED)
+            }
+            _ => maskera_par(rad),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn huvuden(headers: &ureq::http::HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = name.as_str().to_ascii_lowercase();
+            let value = if KANSLIGA_HUVUDEN.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).to_string()
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+fn content_type(headers: &ureq::http::HeaderMap) -> Option<String> {
+    headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or("").trim().to_ascii_lowercase())
+}
+
+fn io_fel(msg: String) -> Error {
+    Error::Io(std::io::Error::other(msg))
+}
+
+/// Sökväg och parametrar, används för att matcha anrop vid uppspelning.
+fn nyckel(method: &str, url: &str) -> String {
+    let path = url
+        .parse::<Uri>()
+        .ok()
+        .and_then(|uri| uri.path_and_query().map(|p| p.to_string()))
+        .unwrap_or_else(|| url.to_string());
+    format!("{} {}", method, path)
+}
+
+/// Jämför två nycklar del för del, där `REDACTED` matchar vad som helst.
+fn matchar(inspelad: &str, anrop: &str) -> bool {
+    let delar = |s: &'_ str| s.split(['/', '?', '&', '=']).map(str::to_string).collect::<Vec<_>>();
+    let (inspelad, anrop) = (delar(inspelad), delar(anrop));
+    inspelad.len() == anrop.len()
+        && inspelad
+            .iter()
+            .zip(anrop.iter())
+            .all(|(a, b)| a == b || a == REDACTED || b == REDACTED)
+}
+
+/// Inspelningens filer heter `0001.json`, `0002.json` och så vidare. Andra
+/// filer i katalogen, t.ex. cachen av verifikat vid uppspelning, ignoreras.
+fn ar_inspelning(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "json")
+        && path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .is_some_and(|s| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Spelar in anrop och svar till en katalog.
+struct Inspelning {
+    katalog: PathBuf,
+    nummer: Mutex<usize>,
+}
+
+impl Inspelning {
+    fn spara(&self, nummer: usize, post: &Post) -> Result<(), Error> {
+        let filename = self.katalog.join(format!("{:04}.json", nummer));
+        let json = serde_json::to_vec_pretty(post).unwrap();
+        std::fs::write(&filename, json)
+            .map_err(|e| io_fel(format!("{}: {}", filename.display(), e)))
+    }
+
+    fn innehall(&self, nummer: usize, headers: &ureq::http::HeaderMap, body: &[u8], spara_binart: bool) -> Result<Innehall, Error> {
+        let mut innehall = Innehall {
+            headers: huvuden(headers),
+            ..Default::default()
+        };
+        if body.is_empty() {
+            return Ok(innehall);
+        }
+
+        let content_type = content_type(headers).unwrap_or_default();
+        match serde_json::from_slice::<Value>(body) {
+            Ok(mut json) => {
+                maskera(&mut json);
+                innehall.json = Some(json);
+            }
+            Err(_) if content_type.starts_with("text/") => {
+                innehall.text = Some(maskera_text(&String::from_utf8_lossy(body)));
+            }
+            Err(_) if spara_binart => {
+                let filename = format!("{:04}.body", nummer);
+                let path = self.katalog.join(&filename);
+                std::fs::write(&path, body).map_err(|e| io_fel(format!("{}: {}", path.display(), e)))?;
+                innehall.body_file = Some(filename);
+            }
+            Err(_) => innehall.body_length = Some(body.len()),
+        }
+        Ok(innehall)
+    }
+}
+
+impl Middleware for Inspelning {
+    fn handle(&self, request: Request<SendBody>, next: MiddlewareNext) -> Result<Response<Body>, Error> {
+        let nummer = {
+            let mut nummer = self.nummer.lock().unwrap();
+            *nummer += 1;
+            *nummer
+        };
+
+        let (parts, body) = request.into_parts();
+        let mut data = Vec::new();
+        std::io::copy(&mut body.into_reader(), &mut data)?;
+        let anrop = Anrop {
+            method: parts.method.to_string(),
+            url: maskera_url(&parts.uri.to_string()),
+            // Uppladdade filer sparas inte, de finns redan hos användaren
+            innehall: self.innehall(nummer, &parts.headers, &data, false)?,
+        };
+
+        let mut slice = data.as_slice();
+        let response = next.handle(Request::from_parts(parts, slice.as_body()))?;
+
+        let (parts, mut body) = response.into_parts();
+        let data = body.read_to_vec()?;
+        let status = parts.status.as_u16();
+        let svar = Svar {
+            status,
+            innehall: self.innehall(nummer, &parts.headers, &data, true)?,
+        };
+        self.spara(nummer, &Post { request: anrop, response: svar })?;
+
+        if status >= 400 {
+            return Err(Error::StatusCode(status));
+        }
+        let body = bygg_body(content_type(&parts.headers), data);
+        Ok(Response::from_parts(parts, body))
+    }
+}
+
+fn bygg_body(content_type: Option<String>, data: Vec<u8>) -> Body {
+    match content_type {
+        Some(content_type) => Body::builder().mime_type(content_type).data(data),
+        None => Body::builder().data(data),
+    }
+}
+
+/// Besvarar anrop från en inspelning.
+struct Uppspelning {
+    katalog: PathBuf,
+    /// Inspelade anrop som ännu inte använts.
+    poster: Mutex<Vec<Post>>,
+}
+
+impl Uppspelning {
+    fn load(katalog: &Path) -> Result<Self, String> {
+        let mut filer = std::fs::read_dir(katalog)
+            .map_err(|e| format!("{}: {}", katalog.display(), e))?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| ar_inspelning(p))
+            .collect::<Vec<_>>();
+        filer.sort();
+
+        let mut poster = Vec::new();
+        for fil in filer {
+            let json = std::fs::read(&fil).map_err(|e| format!("{}: {}", fil.display(), e))?;
+            let post = serde_json::from_slice::<Post>(&json)
+                .map_err(|e| format!("{}: {}", fil.display(), e))?;
+            poster.push(post);
+        }
+        Ok(Uppspelning {
+            katalog: katalog.to_path_buf(),
+            poster: Mutex::new(poster),
+        })
+    }
+
+    fn svar(&self, svar: Svar) -> Result<Response<Body>, Error> {
+        let innehall = svar.innehall;
+        let data = if let Some(json) = innehall.json {
+            serde_json::to_vec(&json).unwrap()
+        } else if let Some(text) = innehall.text {
+            text.into_bytes()
+        } else if let Some(filename) = innehall.body_file {
+            let path = self.katalog.join(filename);
+            std::fs::read(&path).map_err(|e| io_fel(format!("{}: {}", path.display(), e)))?
+        } else {
+            Vec::new()
+        };
+
+        if svar.status >= 400 {
+            return Err(Error::StatusCode(svar.status));
+        }
+        let mut response = Response::builder().status(svar.status);
+        for (name, value) in innehall.headers.iter() {
+            response = response.header(name, value);
+        }
+        let content_type = innehall.headers.get("content-type").map(|v| {
+            v.split(';').next().unwrap_or("").trim().to_string()
+        });
+        Ok(response.body(bygg_body(content_type, data))?)
+    }
+}
+
+impl Middleware for Uppspelning {
+    fn handle(&self, request: Request<SendBody>, _next: MiddlewareNext) -> Result<Response<Body>, Error> {
+        let key = nyckel(request.method().as_str(), &request.uri().to_string());
+        let post = {
+            let mut poster = self.poster.lock().unwrap();
+            let index = poster
+                .iter()
+                .position(|p| matchar(&nyckel(&p.request.method, &p.request.url), &key));
+            index.map(|i| poster.remove(i))
+        };
+        match post {
+            Some(post) => self.svar(post.response),
+            None => Err(io_fel(format!("ingen inspelning för {}", key))),
+        }
+    }
+}
+
+/// Agent som anropar servern direkt.
+pub fn agent() -> Agent {
+    Agent::new_with_defaults()
+}
+
+/// Agent som sparar anrop och svar i `katalog`. Numreringen fortsätter
+/// efter befintliga filer så att flera körningar kan spelas in i följd.
+pub fn agent_spela_in(katalog: &str) -> Result<Agent, String> {
+    std::fs::create_dir_all(katalog).map_err(|e| format!("{}: {}", katalog, e))?;
+    let antal = std::fs::read_dir(katalog)
+        .map_err(|e| format!("{}: {}", katalog, e))?
+        .filter_map(|e| e.ok())
+        .filter(|e| ar_inspelning(&e.path()))
+        .count();
+    let inspelning = Inspelning {
+        katalog: PathBuf::from(katalog),
+        nummer: Mutex::new(antal),
+    };
+    // Statuskoden kontrolleras i inspelningen så att även felsvar sparas
+    Ok(Agent::config_builder()
+        .http_status_as_error(false)
+        .middleware(inspelning)
+        .build()
+        .into())
+}
+
+/// Agent som besvarar anrop från inspelningen i `katalog`.
+pub fn agent_spela_upp(katalog: &str) -> Result<Agent, String> {
+    let uppspelning = Uppspelning::load(Path::new(katalog))?;
+    Ok(Agent::config_builder().middleware(uppspelning).build().into())
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Förväntade nycklar i EasyCashiers JSON för Z-Rapporter.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SchemaBaseline {
+    #[serde(rename = "zReport")]
+    pub z_report: BTreeSet<String>,
+    #[serde(rename = "zReportTransaction")]
+    pub z_report_transaction: BTreeSet<String>,
+    /// Saknas i baslinjer som sparats innan sidinformationen kontrollerades.
+    #[serde(rename = "metaInformation", default = "default_meta_information")]
+    pub meta_information: BTreeSet<String>,
+}
+
+/// Avvikelser för en Z-Rapport i svaret, `rapport` är Z-numret om det finns.
+#[derive(Debug)]
+pub struct RapportAvvikelser {
+    pub rapport: Option<u64>,
+    pub avvikelser: Vec<SchemaAvvikelse>,
+}
+
+/// Avvikelse från baslinjen för ett objekt i svaret.
+#[derive(Debug)]
+pub struct SchemaAvvikelse {
+    /// Var i svaret avvikelsen finns, t.ex. `zReport` eller `zReportTransactions[2]`.
+    pub plats: String,
+    pub ovantade: Vec<String>,
+    pub saknade: Vec<String>,
+}
+
+fn keys(value: &Value) -> BTreeSet<String> {
+    value
+        .as_object()
+        .map(|m| m.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Valfria fält som programmet känner till men som inte alltid skickas.
+const VALFRIA_Z_REPORT: &[&str] = &["storeName", "cashRegisterName"];
+const VALFRIA_Z_REPORT_TRANSACTION: &[&str] = &[
+    "description",
+    "vatCode",
+    "vatRate",
+    "transactionType",
+    "paymentMethod",
+];
+
+fn jamfor(
+    plats: String,
+    faktiska: &BTreeSet<String>,
+    baslinje: &BTreeSet<String>,
+    valfria: &[&str],
+) -> Option<SchemaAvvikelse> {
+    let ovantade = faktiska
+        .difference(baslinje)
+        .filter(|k| !valfria.contains(&k.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    let saknade = baslinje.difference(faktiska).cloned().collect::<Vec<_>>();
+    if ovantade.is_empty() && saknade.is_empty() {
+        None
+    } else {
+        Some(SchemaAvvikelse {
+            plats,
+            ovantade,
+            saknade,
+        })
+    }
+}
+
+fn default_meta_information() -> BTreeSet<String> {
+    ["currentPage", "totalPages", "totalResources"]
+        .iter()
+        .map(|k| k.to_string())
+        .collect()
+}
+
+impl Default for SchemaBaseline {
+    /// Baslinje med de obligatoriska fält som programmet känner till.
+    fn default() -> Self {
+        let set = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect();
+        SchemaBaseline {
+            z_report: set(&[
+                "sequenceNumber",
+                "storeNumber",
+                "cashRegisterNumber",
+                "firstReceipt",
+                "lastReceipt",
+                "dateCreated",
+                "companyName",
+                "corporateIdentity",
+                "zReportTransactions",
+            ]),
+            z_report_transaction: set(&["accountNumber", "amount"]),
+            meta_information: default_meta_information(),
+        }
+    }
+}
+
+impl SchemaBaseline {
+    pub fn load(filename: &str) -> Result<Self, String> {
+        let json = std::fs::read(filename).map_err(|e| format!("{}: {}", filename, e))?;
+        serde_json::from_slice(&json).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(self).unwrap();
+        std::fs::write(filename, json).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    /// Lägg till alla nycklar i rapporten i baslinjen.
+    pub fn uppdatera(&mut self, rapport: &Value) {
+        self.z_report.extend(keys(rapport));
+        for tr in rapport["zReportTransactions"].as_array().into_iter().flatten() {
+            self.z_report_transaction.extend(keys(tr));
+        }
+    }
+
+    /// Lägg till nycklarna i sidinformationen och i alla rapporter på sidan.
+    pub fn uppdatera_sida(&mut self, sida: &Value) {
+        self.meta_information.extend(keys(&sida["metaInformation"]));
+        for rapport in sida["items"].as_array().into_iter().flatten() {
+            self.uppdatera(rapport);
+        }
+    }
+
+    /// Jämför nycklarna i rapporten, som EasyCashier skickade den, med baslinjen.
+    pub fn kontrollera(&self, rapport: &Value) -> Vec<SchemaAvvikelse> {
+        let mut result = Vec::new();
+        result.extend(jamfor(
+            "zReport".to_string(),
+            &keys(rapport),
+            &self.z_report,
+            VALFRIA_Z_REPORT,
+        ));
+        let transaktioner = rapport["zReportTransactions"].as_array().into_iter().flatten();
+        for (i, tr) in transaktioner.enumerate() {
+            result.extend(jamfor(
+                format!("zReportTransactions[{}]", i),
+                &keys(tr),
+                &self.z_report_transaction,
+                VALFRIA_Z_REPORT_TRANSACTION,
+            ));
+        }
+        result
+    }
+
+    /// Jämför sidinformationen med baslinjen.
+    pub fn kontrollera_meta(&self, sida: &Value) -> Option<SchemaAvvikelse> {
+        jamfor(
+            "metaInformation".to_string(),
+            &keys(&sida["metaInformation"]),
+            &self.meta_information,
+            &[],
+        )
+    }
+
+    /// Jämför alla rapporter på en sida ur listningen med baslinjen.
+    pub fn kontrollera_sida(&self, sida: &Value) -> Vec<RapportAvvikelser> {
+        sida["items"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|rapport| RapportAvvikelser {
+                rapport: rapport["sequenceNumber"].as_u64(),
+                avvikelser: self.kontrollera(rapport),
+            })
+            .filter(|r| !r.avvikelser.is_empty())
+            .collect()
+    }
+}
+
+impl std::fmt::Display for SchemaAvvikelse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:", self.plats)?;
+        if !self.ovantade.is_empty() {
+            write!(f, " oväntade nycklar: {}", self.ovantade.join(", "))?;
+        }
+        if !self.saknade.is_empty() {
+            if !self.ovantade.is_empty() {
+                write!(f, ";")?;
+            }
+            write!(f, " saknade nycklar: {}", self.saknade.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use crate::zrapport::ZRapport;
+use std::collections::BTreeMap;
+
+/// Kassa som identifieras med butiksnummer och kassanummer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Kassa {
+    pub store: u32,
+    pub register: u32,
+}
+
+impl std::fmt::Display for Kassa {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bu: {} Ka: {}", self.store, self.register)
+    }
+}
+
+/// Z-nummer som saknas för en kassa, `from` till och med `to`.
+#[derive(Debug)]
+pub struct Lucka {
+    pub kassa: Kassa,
+    pub from: u32,
+    pub to: u32,
+}
+
+impl std::fmt::Display for Lucka {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} Nr: ", self.kassa)?;
+        if self.from == self.to {
+            write!(f, "{}", self.from)
+        } else {
+            write!(f, "{} - {}", self.from, self.to)
+        }
+    }
+}
+
+/// Tolka en kassa som `BUTIK:KASSA`.
+pub fn parse_kassa(s: &str) -> Result<Kassa, String> {
+    let (store, register) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{}: förväntade BUTIK:KASSA", s))?;
+    let store = store.trim().parse::<u32>();
+    let register = register.trim().parse::<u32>();
+    match (store, register) {
+        (Ok(store), Ok(register)) => Ok(Kassa { store, register }),
+        _ => Err(format!("{}: förväntade BUTIK:KASSA", s)),
+    }
+}
+
+pub fn kassa(rapport: &ZRapport) -> Kassa {
+    Kassa {
+        store: rapport.store_number,
+        register: rapport.cash_register_number,
+    }
+}
+
+/// Gruppera rapporterna per kassa, sorterade på Z-nummer.
+pub fn per_kassa<'a>(
+    rapporter: impl IntoIterator<Item = &'a ZRapport>,
+) -> BTreeMap<Kassa, Vec<&'a ZRapport>> {
+    let mut result: BTreeMap<Kassa, Vec<&ZRapport>> = BTreeMap::new();
+    for rapport in rapporter {
+        result.entry(kassa(rapport)).or_default().push(rapport);
+    }
+    for lst in result.values_mut() {
+        lst.sort_by_key(|r| r.sequence_number);
+    }
+    result
+}
+
+/// Hitta saknade Z-nummer per kassa. Med `from` och `to` kontrolleras även
+/// att varje kassas rapporter börjar och slutar på de numren.
+pub fn luckor<'a>(
+    rapporter: impl IntoIterator<Item = &'a ZRapport>,
+    from: Option<u32>,
+    to: Option<u32>,
+) -> Vec<Lucka> {
+    let mut result = Vec::new();
+    for (kassa, lst) in per_kassa(rapporter) {
+        let mut forra = from.and_then(|n| n.checked_sub(1));
+        for rapport in lst {
+            let nr = rapport.sequence_number;
+            if let Some(forra) = forra
+                && nr > forra + 1
+            {
+                result.push(Lucka {
+                    kassa,
+                    from: forra + 1,
+                    to: nr - 1,
+                });
+            }
+            forra = Some(forra.map_or(nr, |f| f.max(nr)));
+        }
+        if let (Some(forra), Some(to)) = (forra, to)
+            && forra < to
+        {
+            result.push(Lucka {
+                kassa,
+                from: forra + 1,
+                to,
+            });
+        }
+    }
+    result
+}
+
+/// Avvikelse i en kassas nummerserie.
+#[derive(Debug)]
+pub enum Avvikelse {
+    /// Z-nummer saknas.
+    Lucka(Lucka),
+    /// Samma Z-nummer förekommer flera gånger.
+    Dubblett { kassa: Kassa, nr: u32 },
+    /// Kvittonummer saknas mellan två rapporter.
+    KvittoLucka {
+        kassa: Kassa,
+        nr: u32,
+        forvantat: u32,
+        forsta: u32,
+    },
+    /// Rapportens kvitton överlappar föregående rapport.
+    KvittoOverlapp {
+        kassa: Kassa,
+        nr: u32,
+        forvantat: u32,
+        forsta: u32,
+    },
+    /// Rapporten är skapad före föregående rapport.
+    Datumordning {
+        kassa: Kassa,
+        nr: u32,
+        datum: String,
+        forra_datum: String,
+    },
+}
+
+impl std::fmt::Display for Avvikelse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Avvikelse::Lucka(lucka) => write!(f, "Z-nummer saknas: {}", lucka),
+            Avvikelse::Dubblett { kassa, nr } => {
+                write!(f, "Z-nummer förekommer flera gånger: {} Nr: {}", kassa, nr)
+            }
+            Avvikelse::KvittoLucka {
+                kassa,
+                nr,
+                forvantat,
+                forsta,
+            } => write!(
+                f,
+                "Kvitton saknas: {} Nr: {} börjar på kvitto {}, förväntade {}",
+                kassa, nr, forsta, forvantat
+            ),
+            Avvikelse::KvittoOverlapp {
+                kassa,
+                nr,
+                forvantat,
+                forsta,
+            } => write!(
+                f,
+                "Kvitton överlappar: {} Nr: {} börjar på kvitto {}, förväntade {}",
+                kassa, nr, forsta, forvantat
+            ),
+            Avvikelse::Datumordning {
+                kassa,
+                nr,
+                datum,
+                forra_datum,
+            } => write!(
+                f,
+                "Datum i fel ordning: {} Nr: {} skapad {} före föregående rapport {}",
+                kassa, nr, datum, forra_datum
+            ),
+        }
+    }
+}
+
+/// Granska nummerserien per kassa: Z-nummer i följd, kvitton i följd
+/// mellan rapporterna och stigande datum.
+pub fn granska<'a>(rapporter: impl IntoIterator<Item = &'a ZRapport>) -> Vec<Avvikelse> {
+    let per_kassa = per_kassa(rapporter);
+    let mut result = luckor(per_kassa.values().flatten().copied(), None, None)
+        .into_iter()
+        .map(Avvikelse::Lucka)
+        .collect::<Vec<_>>();
+
+    for (kassa, lst) in per_kassa {
+        for par in lst.windows(2) {
+            let (forra, rapport) = (par[0], par[1]);
+            let nr = rapport.sequence_number;
+            if nr == forra.sequence_number {
+                result.push(Avvikelse::Dubblett { kassa, nr });
+                continue;
+            }
+
+            let forvantat = forra.last_receipt + 1;
+            let forsta = rapport.first_receipt;
+            if forsta > forvantat {
+                result.push(Avvikelse::KvittoLucka {
+                    kassa,
+                    nr,
+                    forvantat,
+                    forsta,
+                });
+            } else if forsta < forvantat {
+                result.push(Avvikelse::KvittoOverlapp {
+                    kassa,
+                    nr,
+                    forvantat,
+                    forsta,
+                });
+            }
+
+            match (forra.tidpunkt(), rapport.tidpunkt()) {
+                (Ok(forra_tid), Ok(tid)) if tid < forra_tid => {
+                    result.push(Avvikelse::Datumordning {
+                        kassa,
+                        nr,
+                        datum: tid.to_string(),
+                        forra_datum: forra_tid.to_string(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    result
+}
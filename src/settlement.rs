@@ -0,0 +1,422 @@
+//! Utbetalningar från kortinlösare.
+//!
+//! Z-Rapporten debiterar 1580 för kortförsäljning men pengarna kommer
+//! till banken några dagar senare, minus inlösarens avgifter. Här tolkas
+//! inlösarens avräkningsfil (CSV från Nets, Worldline eller Bambora) och
+//! varje utbetalning kopplas till Z-Rapporterna för de dagar den avser.
+//! Verifikatet för en utbetalning krediterar 1580 med bruttobeloppet och
+//! debiterar bankkontot med nettobeloppet och avgiftskontot med avgiften.
+
+use crate::ledger::{CreateJournal, CreateJournalAccount, JournalEntry};
+use crate::zrapport::{ZRapport, ZRapportId};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/// Konto för kortfordringar i Z-Rapporten.
+pub const KORTFORDRINGAR: u16 = 1580;
+pub const BANKKONTO: u16 = 1930;
+pub const AVGIFTSKONTO: u16 = 6570;
+
+/// Början på verifikatnamn för utbetalningar.
+pub const TITELPREFIX: &str = "Kortinlösen, ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Inlosare {
+    Nets,
+    Worldline,
+    Bambora,
+}
+
+impl std::fmt::Display for Inlosare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let namn = match self {
+            Inlosare::Nets => "Nets",
+            Inlosare::Worldline => "Worldline",
+            Inlosare::Bambora => "Bambora",
+        };
+        write!(f, "{}", namn)
+    }
+}
+
+/// Kolumner i en inlösares avräkningsfil.
+struct Filformat {
+    inlosare: Inlosare,
+    utbetalningsdatum: &'static str,
+    transaktionsdatum: &'static str,
+    referens: &'static str,
+    brutto: &'static str,
+    avgift: &'static str,
+    netto: &'static str,
+}
+
+/// Kolumnnamnen är hämtade ur inlösarnas beskrivningar av avräkningsfilerna,
+/// inte ur riktiga exporter, och testerna använder egna exempelfiler. Känns
+/// filen inte igen skrivs dess kolumner ut så att formatet kan läggas till här.
+const FILFORMAT: [Filformat; 3] = [
+    Filformat {
+        inlosare: Inlosare::Nets,
+        utbetalningsdatum: "Settlement date",
+        transaktionsdatum: "Transaction date",
+        referens: "Settlement reference",
+        brutto: "Gross amount",
+        avgift: "Fee amount",
+        netto: "Net amount",
+    },
+    Filformat {
+        inlosare: Inlosare::Worldline,
+        utbetalningsdatum: "Payout date",
+        transaktionsdatum: "Transaction date",
+        referens: "Payout reference",
+        brutto: "Gross amount",
+        avgift: "Commission",
+        netto: "Net amount",
+    },
+    Filformat {
+        inlosare: Inlosare::Bambora,
+        utbetalningsdatum: "Utbetalningsdatum",
+        transaktionsdatum: "Transaktionsdatum",
+        referens: "Utbetalningsreferens",
+        brutto: "Belopp",
+        avgift: "Avgift",
+        netto: "Utbetalt belopp",
+    },
+];
+
+/// En utbetalning från inlösaren, summerad från filens rader.
+#[derive(Debug)]
+pub struct Utbetalning {
+    pub inlosare: Inlosare,
+    pub referens: String,
+    /// Dagen pengarna kommer till banken.
+    pub datum: NaiveDate,
+    /// Dagarna då korttransaktionerna gjordes.
+    pub transaktionsdatum: BTreeSet<NaiveDate>,
+    pub brutto: Decimal,
+    pub avgift: Decimal,
+    pub netto: Decimal,
+}
+
+impl Utbetalning {
+    /// Verifikatnamn, t.ex. `Kortinlösen, Nets 123456`.
+    pub fn titel(&self) -> String {
+        format!("{}{} {}", TITELPREFIX, self.inlosare, self.referens)
+    }
+}
+
+/// Dela en rad i fält. Fält inom citattecken kan innehålla avgränsaren
+/// och dubbla citattecken.
+fn falt(rad: &str, avgransare: char) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut varde = String::new();
+    let mut citat = false;
+    let mut tecken = rad.chars().peekable();
+    while let Some(c) = tecken.next() {
+        match c {
+            '"' if citat && tecken.peek() == Some(&'"') => {
+                varde.push('"');
+                tecken.next();
+            }
+            '"' => citat = !citat,
+            c if c == avgransare && !citat => result.push(std::mem::take(&mut varde)),
+            c => varde.push(c),
+        }
+    }
+    result.push(varde);
+    result.iter().map(|s| s.trim().to_string()).collect()
+}
+
+/// Tolka ett belopp. Det sista av punkt och komma är decimaltecknet och det
+/// andra, liksom mellanslag, tusentalsavgränsare. Ett ensamt tecken följt av
+/// tre siffror, som `1,250`, kan vara båda och ger fel.
+fn belopp(s: &str) -> Result<Decimal, String> {
+    let ogiltigt = || format!("ogiltigt belopp {:?}", s);
+    // Grupperna efter den första tusentalsavgränsaren har tre siffror
+    let grupperat = |text: &str, avgransare: char| text.split(avgransare).skip(1).all(|g| g.len() == 3);
+    let text = s.replace([' ', '\u{a0}'], "");
+    let text = match text.rfind([',', '.']) {
+        None => text,
+        Some(i) => {
+            let (heltal, decimaler) = (&text[..i], &text[i + 1..]);
+            let (tecken, annat) = if text[i..].starts_with(',') { (',', '.') } else { ('.', ',') };
+            if heltal.contains(tecken) {
+                // Samma tecken flera gånger är tusentalsavgränsare, t.ex. 1.250.000
+                if heltal.contains(annat) || !grupperat(&text, tecken) {
+                    return Err(ogiltigt());
+                }
+                text.replace(tecken, "")
+            } else if heltal.contains(annat) {
+                if !grupperat(heltal, annat) {
+                    return Err(ogiltigt());
+                }
+                format!("{}.{}", heltal.replace(annat, ""), decimaler)
+            } else if decimaler.len() == 3 {
+                return Err(format!("beloppet {:?} kan vara med decimaler eller tusentalsavgränsare", s));
+            } else {
+                format!("{}.{}", heltal, decimaler)
+            }
+        }
+    };
+    let varde = Decimal::from_str(&text).map_err(|_| ogiltigt())?;
+    if varde.normalize().scale() > crate::amount::SCALE {
+        return Err(format!("beloppet {} har fler decimaler än öre", s));
+    }
+    Ok(varde)
+}
+
+/// Tolka ett datum som `YYYY-MM-DD` eller `DD.MM.YYYY`, med eller utan klockslag.
+fn datum(s: &str) -> Result<NaiveDate, String> {
+    let dag = s.split([' ', 'T']).next().unwrap_or_default();
+    NaiveDate::parse_from_str(dag, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(dag, "%d.%m.%Y"))
+        .map_err(|_| format!("ogiltigt datum {:?}", s))
+}
+
+/// Tolka en avräkningsfil. Inlösaren känns igen på kolumnnamnen och
+/// rader med samma utbetalningsdatum och referens läggs ihop.
+pub fn tolka(text: &str) -> Result<Vec<Utbetalning>, String> {
+    let mut rader = text
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .filter(|(_, rad)| !rad.trim().is_empty());
+    let (_, rubrik) = rader.next().ok_or("filen är tom")?;
+    let avgransare = [';', '\t', ',']
+        .into_iter()
+        .max_by_key(|c| rubrik.matches(*c).count())
+        .unwrap();
+    let rubriker = falt(rubrik, avgransare);
+    let kolumn = |namn: &str| rubriker.iter().position(|r| r.eq_ignore_ascii_case(namn));
+
+    let (format, index) = FILFORMAT
+        .iter()
+        .find_map(|f| {
+            let index = [
+                kolumn(f.utbetalningsdatum)?,
+                kolumn(f.transaktionsdatum)?,
+                kolumn(f.referens)?,
+                kolumn(f.brutto)?,
+                kolumn(f.avgift)?,
+                kolumn(f.netto)?,
+            ];
+            Some((f, index))
+        })
+        .ok_or_else(|| format!("okänt filformat, kolumner: {}", rubriker.join(", ")))?;
+
+    let mut result: Vec<Utbetalning> = Vec::new();
+    for (nr, rad) in rader {
+        let varden = falt(rad, avgransare);
+        let tolka_rad = || -> Result<_, String> {
+            let varde = |i: usize| varden.get(index[i]).map(String::as_str).unwrap_or_default();
+            Ok((
+                datum(varde(0))?,
+                datum(varde(1))?,
+                varde(2).to_string(),
+                belopp(varde(3))?,
+                // Avgiften anges som negativt belopp hos vissa inlösare
+                belopp(varde(4))?.abs(),
+                belopp(varde(5))?,
+            ))
+        };
+        let (utbetalningsdatum, transaktionsdatum, referens, brutto, avgift, netto) =
+            tolka_rad().map_err(|e| format!("rad {}: {}", nr + 1, e))?;
+        if brutto - avgift != netto {
+            return Err(format!(
+                "rad {}: nettobeloppet {} är inte bruttobeloppet {} minus avgiften {}",
+                nr + 1,
+                netto,
+                brutto,
+                avgift
+            ));
+        }
+
+        let utbetalning = result
+            .iter_mut()
+            .find(|u| u.datum == utbetalningsdatum && u.referens == referens);
+        match utbetalning {
+            Some(u) => {
+                u.transaktionsdatum.insert(transaktionsdatum);
+                u.brutto += brutto;
+                u.avgift += avgift;
+                u.netto += netto;
+            }
+            None => result.push(Utbetalning {
+                inlosare: format.inlosare,
+                referens,
+                datum: utbetalningsdatum,
+                transaktionsdatum: BTreeSet::from([transaktionsdatum]),
+                brutto,
+                avgift,
+                netto,
+            }),
+        }
+    }
+    result.sort_by(|a, b| a.datum.cmp(&b.datum).then_with(|| a.referens.cmp(&b.referens)));
+    Ok(result)
+}
+
+pub fn las(filename: &str) -> Result<Vec<Utbetalning>, String> {
+    let bytes = std::fs::read(filename).map_err(|e| format!("{}: {}", filename, e))?;
+    let text = String::from_utf8(bytes).map_err(|_| format!("{}: filen är inte UTF-8", filename))?;
+    tolka(&text).map_err(|e| format!("{}: {}", filename, e))
+}
+
+/// Utbetalningar som delar transaktionsdatum och Z-Rapporterna för dagarna.
+pub struct Koppling<'a> {
+    pub utbetalningar: Vec<&'a Utbetalning>,
+    pub rapporter: Vec<&'a ZRapport>,
+    /// Summan av 1580 i rapporterna.
+    pub kort: Decimal,
+    /// Bruttobelopp som redan bokförts mot rapporterna av andra utbetalningar.
+    pub bokfort: Decimal,
+}
+
+impl Koppling<'_> {
+    /// Kortförsäljningen i Z-Rapporterna som inte redan bokförts mot en utbetalning.
+    pub fn kvar(&self) -> Decimal {
+        self.kort - self.bokfort
+    }
+
+    /// Summan av bruttobeloppen minus kortförsäljningen som är kvar.
+    pub fn differens(&self) -> Decimal {
+        self.utbetalningar.iter().map(|u| u.brutto).sum::<Decimal>() - self.kvar()
+    }
+}
+
+/// En bokförd utbetalning: Z-Rapporterna och bruttobeloppet på raden för
+/// 1580, som anger rapporterna som `Z-Rapport BUTIK:KASSA:NUMMER, ...`.
+#[derive(Debug)]
+pub struct Tackning {
+    pub rapporter: Vec<ZRapportId>,
+    pub brutto: Decimal,
+}
+
+/// Z-Rapporterna och bruttobeloppen i redan bokförda utbetalningar.
+/// Verifikaten måste ha sina rader, se `LedgerSink::entry`.
+pub fn tackningar(bokforda: &[JournalEntry]) -> Vec<Tackning> {
+    bokforda
+        .iter()
+        .filter(|e| e.reversed_by_journal_entry_id.is_none())
+        .map(|e| {
+            let rader = e.items.iter().filter(|item| item.account == KORTFORDRINGAR as i32);
+            Tackning {
+                rapporter: rader
+                    .clone()
+                    .filter_map(|item| item.description.as_deref()?.strip_prefix("Z-Rapport "))
+                    .flat_map(|lista| lista.split(',').filter_map(|id| id.parse::<ZRapportId>().ok()))
+                    .collect(),
+                brutto: rader.map(|item| item.credit - item.debit).sum(),
+            }
+        })
+        .filter(|t| !t.rapporter.is_empty())
+        .collect()
+}
+
+/// Dela upp utbetalningarna i grupper som delar transaktionsdatum och koppla
+/// varje grupp till Z-Rapporterna för dagarna. En dag kan betalas ut i flera
+/// utbetalningar, t.ex. en per kortsort eller från olika inlösare, så det är
+/// gruppens summa som ska stämma med kortförsäljningen. Utbetalningar som
+/// redan bokförts mot rapporterna dras av från kortförsäljningen, så att
+/// resten av en dag kan bokföras i en senare körning.
+pub fn koppla<'a>(
+    utbetalningar: &[&'a Utbetalning],
+    rapporter: &'a [ZRapport],
+    tackningar: &[Tackning],
+    brytpunkt: u32,
+) -> Vec<Koppling<'a>> {
+    let mut grupper: Vec<(BTreeSet<NaiveDate>, Vec<&'a Utbetalning>)> = Vec::new();
+    for u in utbetalningar {
+        let mut dagar = u.transaktionsdatum.clone();
+        let mut grupp = vec![*u];
+        // Slå ihop alla grupper som delar någon dag med utbetalningen
+        let (delar, ovriga) = std::mem::take(&mut grupper)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(d, _)| !d.is_disjoint(&dagar));
+        for (d, g) in delar {
+            dagar.extend(d);
+            grupp.extend(g);
+        }
+        grupper = ovriga;
+        grupp.sort_by(|a, b| a.datum.cmp(&b.datum).then_with(|| a.referens.cmp(&b.referens)));
+        grupper.push((dagar, grupp));
+    }
+    grupper.sort_by(|a, b| a.1[0].datum.cmp(&b.1[0].datum).then_with(|| a.1[0].referens.cmp(&b.1[0].referens)));
+
+    grupper
+        .into_iter()
+        .map(|(dagar, utbetalningar)| {
+            let mut valda = rapporter
+                .iter()
+                .filter(|r| r.datum(brytpunkt).is_ok_and(|d| dagar.contains(&d)))
+                .collect::<Vec<_>>();
+            // En bokförd utbetalning kan avse fler rapporter än dagarna, de
+            // tas med så att hela dess bruttobelopp har rapporter att dras från
+            let mut anvanda = vec![false; tackningar.len()];
+            while let Some(i) = (0..tackningar.len())
+                .find(|i| !anvanda[*i] && valda.iter().any(|r| tackningar[*i].rapporter.contains(&r.id())))
+            {
+                anvanda[i] = true;
+                for r in rapporter.iter().filter(|r| tackningar[i].rapporter.contains(&r.id())) {
+                    if !valda.iter().any(|v| v.id() == r.id()) {
+                        valda.push(r);
+                    }
+                }
+            }
+            valda.sort_by_key(|r| (r.store_number, r.cash_register_number, r.sequence_number));
+            let kort = valda.iter().map(|r| r.konto(KORTFORDRINGAR)).sum();
+            let bokfort = tackningar
+                .iter()
+                .zip(anvanda)
+                .filter(|(_, anvand)| *anvand)
+                .map(|(t, _)| t.brutto)
+                .sum();
+            Koppling {
+                utbetalningar,
+                rapporter: valda,
+                kort,
+                bokfort,
+            }
+        })
+        .collect()
+}
+
+/// Positiva belopp debiteras och negativa krediteras.
+fn rad(account: u16, varde: Decimal, description: Option<String>) -> CreateJournalAccount {
+    CreateJournalAccount {
+        account: account as i32,
+        debit: varde.max(Decimal::ZERO),
+        credit: (-varde).max(Decimal::ZERO),
+        description,
+    }
+}
+
+/// Verifikat som nollar 1580 mot bankkontot och avgiftskontot. Raden för
+/// 1580 anger Z-Rapporterna så att de inte räknas två gånger, se `tackningar`.
+pub fn create_journal_entry(
+    utbetalning: &Utbetalning,
+    koppling: &Koppling,
+    bankkonto: u16,
+    avgiftskonto: u16,
+) -> CreateJournal {
+    let rapporter = koppling
+        .rapporter
+        .iter()
+        .map(|r| r.id().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut items = vec![rad(bankkonto, utbetalning.netto, None)];
+    if !utbetalning.avgift.is_zero() {
+        items.push(rad(avgiftskonto, utbetalning.avgift, None));
+    }
+    items.push(rad(
+        KORTFORDRINGAR,
+        -utbetalning.brutto,
+        Some(format!("Z-Rapport {}", rapporter)).filter(|_| !rapporter.is_empty()),
+    ));
+    CreateJournal {
+        title: utbetalning.titel(),
+        date: utbetalning.datum,
+        items,
+    }
+}
@@ -0,0 +1,168 @@
+use crate::ledger::{Account, CreateJournalAccount, JournalEntry, JournalEntryAccount};
+use crate::mapping::Mapping;
+use crate::utils::format_local_date;
+use crate::{RapportImport, create_journal_entry};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+fn kontonamn(kontoplan: &[Account], account: i32) -> &str {
+    kontoplan
+        .iter()
+        .find(|a| a.account == account)
+        .map_or("(saknas i kontoplanen)", |a| a.name.as_str())
+}
+
+fn ovriga_falt(other: &std::collections::HashMap<String, Value>) -> BTreeMap<&str, String> {
+    other.iter().map(|(k, v)| (k.as_str(), v.to_string())).collect()
+}
+
+/// Skriv ut alla uppgifter om en Z-Rapport: huvudfält, transaktioner med
+/// kontonamn, okända fält från EasyCashier, föreslaget verifikat och
+/// skillnader mot verifikatet i Bokio om rapporten redan är bokförd.
+pub fn visa_rapport(import: &RapportImport, mapping: &Mapping, kontoplan: &[Account]) {
+    let rapport = &import.rapport;
+    let namn = |namn: &Option<String>| namn.as_ref().map(|n| format!(" ({})", n)).unwrap_or_default();
+    println!("Z-Rapport {}", rapport.id());
+    println!("  Butik:           {}{}", rapport.store_number, namn(&rapport.store_name));
+    println!("  Kassa:           {}{}", rapport.cash_register_number, namn(&rapport.cash_register_name));
+    println!("  Z-nummer:        {}", rapport.sequence_number);
+    println!("  Kvitton:         {} - {}", rapport.first_receipt, rapport.last_receipt);
+    match rapport.tidpunkt() {
+        Ok(tid) => println!("  Skapad:          {} (svensk tid {})", rapport.date_created, tid),
+        Err(_) => println!("  Skapad:          {}", rapport.date_created),
+    }
+    println!("  Företag:         {} ({})", rapport.company_name, rapport.corporate_identity);
+    println!(
+        "  Bokföringsdatum: {}",
+        import.datum.as_ref().map_or("?".to_string(), format_local_date)
+    );
+    println!("  Verifikatnamn:   {}", import.titel);
+    match (&import.verifikat, &import.stangd) {
+        (Some(verifikat), _) => println!("  Status:          bokförd som {}", verifikat.journal_entry_number),
+        (None, Some(stangd)) => println!("  Status:          kan inte bokföras: {}", stangd),
+        (None, None) => println!("  Status:          inte bokförd"),
+    }
+    for (key, value) in ovriga_falt(&rapport.other) {
+        println!("  {}: {}", key, value);
+    }
+
+    println!();
+    println!("TRANSAKTIONER");
+    for tr in rapport.z_report_transactions.iter() {
+        let account = tr.account_number as i32;
+        println!(
+            "  {:>5} {:<30} {:>12.2}  {}",
+            account,
+            kontonamn(kontoplan, account),
+            tr.amount,
+            mapping.text(tr).unwrap_or_default()
+        );
+        let mut detaljer = Vec::new();
+        if let Some(t) = &tr.transaction_type {
+            detaljer.push(format!("typ {}", String::from(t.clone())));
+        }
+        if let Some(p) = &tr.payment_method {
+            detaljer.push(format!("betalsätt {}", String::from(p.clone())));
+        }
+        if let Some(rate) = tr.vat_rate {
+            detaljer.push(format!("moms {}%", rate.normalize()));
+        }
+        if let Some(code) = &tr.vat_code {
+            detaljer.push(format!("momskod {}", code));
+        }
+        detaljer.extend(ovriga_falt(&tr.other).into_iter().map(|(k, v)| format!("{}={}", k, v)));
+        if !detaljer.is_empty() {
+            println!("        {}", detaljer.join(", "));
+        }
+    }
+
+    let Some(datum) = import.datum else {
+        return;
+    };
+    let forslag = create_journal_entry(rapport, &import.titel, datum, mapping);
+    println!();
+    println!("FÖRESLAGET VERIFIKAT {} {}", format_local_date(&forslag.date), forslag.title);
+    println!("  {:>5} {:<30} {:>12} {:>12}", "KONTO", "NAMN", "DEBET", "KREDIT");
+    for item in forslag.items.iter() {
+        println!(
+            "  {:>5} {:<30} {:>12.2} {:>12.2}  {}",
+            item.account,
+            kontonamn(kontoplan, item.account),
+            item.debit,
+            item.credit,
+            item.description.as_deref().unwrap_or("")
+        );
+    }
+
+    if let Some(verifikat) = &import.verifikat {
+        println!();
+        visa_skillnader(&forslag.items, verifikat);
+    }
+}
+
+/// Rad i jämförelsen mellan föreslaget och bokfört verifikat.
+enum Skillnad<'a> {
+    Lika(&'a CreateJournalAccount),
+    Andrad(&'a CreateJournalAccount, &'a JournalEntryAccount),
+    BaraForslag(&'a CreateJournalAccount),
+    BaraBokio(&'a JournalEntryAccount),
+}
+
+/// Para ihop raderna per konto, i ordning. Rader som bara finns på ena
+/// sidan redovisas separat.
+fn jamfor<'a>(forslag: &'a [CreateJournalAccount], bokfort: &'a [JournalEntryAccount]) -> Vec<Skillnad<'a>> {
+    let mut anvanda = vec![false; bokfort.len()];
+    let mut result = Vec::new();
+    for item in forslag {
+        let par = (0..bokfort.len()).find(|i| !anvanda[*i] && bokfort[*i].account == item.account);
+        match par {
+            Some(i) => {
+                anvanda[i] = true;
+                let b = &bokfort[i];
+                if b.debit == item.debit && b.credit == item.credit {
+                    result.push(Skillnad::Lika(item));
+                } else {
+                    result.push(Skillnad::Andrad(item, b));
+                }
+            }
+            None => result.push(Skillnad::BaraForslag(item)),
+        }
+    }
+    for (b, _) in bokfort.iter().zip(anvanda).filter(|(_, anvand)| !anvand) {
+        result.push(Skillnad::BaraBokio(b));
+    }
+    result
+}
+
+fn visa_skillnader(forslag: &[CreateJournalAccount], verifikat: &JournalEntry) {
+    println!(
+        "BOKFÖRT VERIFIKAT {} {} {}",
+        verifikat.journal_entry_number,
+        format_local_date(&verifikat.date),
+        verifikat.title
+    );
+    let skillnader = jamfor(forslag, &verifikat.items);
+    let rad = |tecken: &str, account: i32, debit: Decimal, credit: Decimal, kommentar: String| {
+        println!("{} {:>5} {:>12.2} {:>12.2}  {}", tecken, account, debit, credit, kommentar);
+    };
+    for s in skillnader.iter() {
+        match s {
+            Skillnad::Lika(f) => rad(" ", f.account, f.debit, f.credit, String::new()),
+            Skillnad::Andrad(f, b) => rad(
+                "~",
+                b.account,
+                b.debit,
+                b.credit,
+                format!("föreslaget {:.2} / {:.2}", f.debit, f.credit),
+            ),
+            Skillnad::BaraForslag(f) => rad("+", f.account, f.debit, f.credit, "saknas i Bokio".to_string()),
+            Skillnad::BaraBokio(b) => rad("-", b.account, b.debit, b.credit, "finns bara i Bokio".to_string()),
+        }
+    }
+    if skillnader.iter().all(|s| matches!(s, Skillnad::Lika(_))) {
+        println!("Verifikatet stämmer med Z-Rapporten.");
+    } else {
+        println!("Verifikatet avviker från Z-Rapporten (~ ändrad, + saknas i Bokio, - finns bara i Bokio).");
+    }
+}
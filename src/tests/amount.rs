@@ -0,0 +1,38 @@
+use crate::zrapport::ZRapportTrans;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::str::FromStr;
+
+fn tolka(json: &str) -> Result<ZRapportTrans, serde_json::Error> {
+    // Rapporterna tolkas både direkt och via `Value` vid schemakontrollen
+    let direkt = serde_json::from_str::<ZRapportTrans>(json);
+    let via_value = serde_json::from_value::<ZRapportTrans>(serde_json::from_str::<Value>(json).unwrap());
+    assert_eq!(
+        direkt.as_ref().map(|t| (t.amount, t.vat_rate)).ok(),
+        via_value.as_ref().map(|t| (t.amount, t.vat_rate)).ok()
+    );
+    direkt
+}
+
+#[test]
+fn belopp_tolkas_fran_talets_text() {
+    for belopp in ["1234567890123456.78", "0.1", "-0.07", "100", "1e2"] {
+        let json = format!(r#"{{"accountNumber": 3001, "amount": {}}}"#, belopp);
+        let tr = tolka(&json).unwrap();
+        let forvantat = Decimal::from_str(belopp).or_else(|_| Decimal::from_scientific(belopp)).unwrap();
+        assert_eq!(tr.amount, forvantat, "{}", belopp);
+    }
+    assert!(tolka(r#"{"accountNumber": 3001, "amount": 0.005}"#).is_err());
+}
+
+#[test]
+fn momssats_far_ha_fler_decimaler_an_belopp() {
+    let tr = tolka(r#"{"accountNumber": 3001, "amount": 10.00, "vatRate": 6.125}"#).unwrap();
+    assert_eq!(tr.vat_rate, Some(Decimal::from_str("6.125").unwrap()));
+    let json = serde_json::to_value(&tr).unwrap();
+    assert_eq!(json["vatRate"].to_string(), "6.125");
+    assert_eq!(json["amount"].to_string(), "10.00");
+
+    let tr = tolka(r#"{"accountNumber": 3001, "amount": 10, "vatRate": null}"#).unwrap();
+    assert_eq!(tr.vat_rate, None);
+}
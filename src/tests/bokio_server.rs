@@ -0,0 +1,166 @@
+use super::{Anrop, FakeServer, Svar};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+pub const TOKEN: &str = "bokio-token";
+pub const COMPANY_ID: &str = "00000000-0000-0000-0000-000000000001";
+
+#[derive(Default)]
+pub struct State {
+    /// Verifikat i Bokios JSON-format, i den ordning de skapades.
+    pub verifikat: Vec<Value>,
+    /// Uppladdade underlag, `(verifikat-id, filnamn)`.
+    pub underlag: Vec<(String, String)>,
+    /// Sidor som hämtats från listningen av verifikat.
+    pub sidor: Vec<u32>,
+    /// Filter i varje anrop till listningen av verifikat.
+    pub filter: Vec<String>,
+    /// Svara med serverfel när verifikat skapas.
+    pub fel_vid_bokforing: bool,
+    /// Svara med serverfel vid uppladdning.
+    pub fel_vid_uppladdning: bool,
+}
+
+/// Låtsasserver för Bokio med företag, räkenskapsår, kontoplan,
+/// listning och skapande av verifikat samt uppladdning av underlag.
+pub struct FakeBokio {
+    pub server: FakeServer,
+    pub state: Arc<Mutex<State>>,
+}
+
+/// Verifikat som det ser ut i Bokio.
+pub fn verifikat(id: &str, nummer: u32, title: &str, date: &str) -> Value {
+    json!({
+        "id": id,
+        "title": title,
+        "journalEntryNumber": format!("V{}", nummer),
+        "date": date,
+        "items": [],
+        "reversingJournalEntryId": null,
+        "reversedByJournalEntryId": null,
+    })
+}
+
+/// Hämta ett fält ur en multipart-kropp.
+fn multipart_falt(body: &[u8], name: &str) -> Option<String> {
+    let body = String::from_utf8_lossy(body);
+    let start = body.find(&format!("name=\"{}\"", name))?;
+    let rest = &body[start..];
+    let value = &rest[rest.find("\r\n\r\n")? + 4..];
+    Some(value[..value.find("\r\n")?].to_string())
+}
+
+fn hantera(state: &Mutex<State>, orgnr: &str, anrop: Anrop) -> Svar {
+    if anrop.headers.get("authorization") != Some(&format!("Bearer {}", TOKEN)) {
+        return Svar::status(401);
+    }
+
+    let prefix = format!("/companies/{}", COMPANY_ID);
+    let Some(rest) = anrop.path.strip_prefix(&prefix) else {
+        return Svar::status(404);
+    };
+    let mut state = state.lock().unwrap();
+
+    match (anrop.method.as_str(), rest) {
+        ("GET", "") => Svar::json(&json!({
+            "id": COMPANY_ID,
+            "name": "Testbolaget AB",
+            "organizationNumber": orgnr,
+        })),
+        ("GET", "/fiscal-years") => Svar::json(&json!({
+            "totalItems": 1,
+            "totalPages": 1,
+            "currentPage": 1,
+            "items": [{
+                "id": "fy2025",
+                "startDate": "2025-01-01",
+                "endDate": "2025-12-31",
+                "status": "open",
+                "lockedUntil": null,
+            }],
+        })),
+        ("GET", "/chart-of-accounts") => Svar::json(&json!([
+            { "account": 1580, "name": "Fordringar för kontokort", "isActive": true },
+            { "account": 1911, "name": "Kassa", "isActive": true },
+            { "account": 2611, "name": "Utgående moms 25%", "isActive": true },
+            { "account": 3001, "name": "Försäljning 25%", "isActive": true },
+        ])),
+        ("GET", "/journal-entries") => {
+            let page = anrop.query["page"].parse::<u32>().unwrap();
+            let size = anrop.query["pageSize"].parse::<u32>().unwrap();
+            state.sidor.push(page);
+            let filter = anrop.query.get("query").cloned().unwrap_or_default();
+            state.filter.push(filter);
+
+            // Bokio listar senaste verifikatet först
+            let mut verifikat = state.verifikat.clone();
+            verifikat.reverse();
+            verifikat.sort_by(|a, b| b["date"].as_str().cmp(&a["date"].as_str()));
+            let total = verifikat.len() as u32;
+            let items = verifikat
+                .into_iter()
+                .skip(((page - 1) * size) as usize)
+                .take(size as usize)
+                .collect::<Vec<_>>();
+            Svar::json(&json!({
+                "totalItems": total,
+                "totalPages": total.div_ceil(size),
+                "currentPage": page,
+                "items": items,
+            }))
+        }
+        ("POST", "/journal-entries") => {
+            if state.fel_vid_bokforing {
+                return Svar::status(500);
+            }
+            let mut entry = serde_json::from_slice::<Value>(&anrop.body).unwrap();
+            let nummer = state.verifikat.len() as u32 + 1;
+            let id = format!("je-{}", nummer);
+            for (i, item) in entry["items"].as_array_mut().unwrap().iter_mut().enumerate() {
+                item["id"] = json!(i + 1);
+            }
+            entry["id"] = json!(id);
+            entry["journalEntryNumber"] = json!(format!("V{}", nummer));
+            entry["reversingJournalEntryId"] = Value::Null;
+            entry["reversedByJournalEntryId"] = Value::Null;
+            state.verifikat.push(entry.clone());
+            Svar::json(&entry)
+        }
+        ("POST", "/uploads") => {
+            if state.fel_vid_uppladdning {
+                return Svar::status(500);
+            }
+            let journal_entry_id = multipart_falt(&anrop.body, "journalEntryId").unwrap();
+            let body = String::from_utf8_lossy(&anrop.body);
+            let filename = body
+                .split("filename=\"")
+                .nth(1)
+                .and_then(|s| s.split('"').next())
+                .unwrap_or_default()
+                .to_string();
+            state.underlag.push((journal_entry_id.clone(), filename.clone()));
+            Svar::json(&json!({
+                "id": format!("upload-{}", state.underlag.len()),
+                "description": filename,
+                "contentType": "application/pdf",
+                "journalEntryId": journal_entry_id,
+            }))
+        }
+        _ => Svar::status(404),
+    }
+}
+
+impl FakeBokio {
+    pub fn start(orgnr: &str, verifikat: Vec<Value>) -> Self {
+        let state = Arc::new(Mutex::new(State {
+            verifikat,
+            ..Default::default()
+        }));
+        let server = {
+            let state = state.clone();
+            let orgnr = orgnr.to_string();
+            FakeServer::start(move |anrop| hantera(&state, &orgnr, anrop))
+        };
+        FakeBokio { server, state }
+    }
+}
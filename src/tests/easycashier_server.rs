@@ -0,0 +1,128 @@
+use super::{Anrop, FakeServer, Svar};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+pub const TOKEN: &str = "easycashier-token";
+pub const PDF: &[u8] = b"%PDF-1.4 Z-Rapport";
+
+#[derive(Default)]
+pub struct State {
+    /// Z-Rapporter i EasyCashiers JSON-format.
+    pub rapporter: Vec<Value>,
+    /// Sidor som hämtats från listningen, `(sida, antal per sida)`.
+    pub sidor: Vec<(u32, u32)>,
+}
+
+/// Låtsasserver för EasyCashier med inloggning, listning av Z-Rapporter
+/// med sidindelning, enskild Z-Rapport och PDF.
+pub struct FakeEasyCashier {
+    pub server: FakeServer,
+    pub state: Arc<Mutex<State>>,
+}
+
+/// Z-Rapport med försäljning på 25% moms som betalats med kort, `kort`
+/// ska vara delbart med 5.
+pub fn zrapport(orgnr: &str, store: u32, nr: u32, date_created: &str, kort: i64) -> Value {
+    let moms = kort / 5;
+    json!({
+        "sequenceNumber": nr,
+        "storeNumber": store,
+        "cashRegisterNumber": 1,
+        "firstReceipt": nr * 10,
+        "lastReceipt": nr * 10 + 9,
+        "dateCreated": date_created,
+        "companyName": "Testbolaget AB",
+        "corporateIdentity": orgnr,
+        "zReportTransactions": [
+            { "accountNumber": 1580, "amount": kort },
+            { "accountNumber": 3001, "amount": moms - kort },
+            { "accountNumber": 2611, "amount": -moms },
+        ],
+    })
+}
+
+fn hantera(state: &Mutex<State>, orgnr: &str, anrop: Anrop) -> Svar {
+    if anrop.method == "POST" && anrop.path == "/v1/login" {
+        return Svar::json(&json!({
+            "accessToken": TOKEN,
+            "preferredCorporateIdentity": orgnr,
+        }));
+    }
+
+    if anrop.headers.get("x-auth-token").map(|t| t.as_str()) != Some(TOKEN) {
+        return Svar::status(401);
+    }
+
+    let prefix = format!("/v1/company/{}/zReport", orgnr);
+    let Some(rest) = anrop.path.strip_prefix(&prefix) else {
+        return Svar::status(404);
+    };
+    let mut state = state.lock().unwrap();
+
+    if rest.is_empty() {
+        let param = |name: &str| anrop.query.get(name).cloned().unwrap_or_default();
+        let size = param("itemsPerPage").parse::<u32>().unwrap();
+        let page = param("pageNumber").parse::<u32>().unwrap();
+        let (start, stop) = (param("startDate"), param("stopDate"));
+        state.sidor.push((page, size));
+
+        let mut rapporter = state
+            .rapporter
+            .iter()
+            .filter(|r| {
+                let datum = &r["dateCreated"].as_str().unwrap()[..10];
+                start.as_str() <= datum && datum <= stop.as_str()
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        rapporter.sort_by_key(|r| r["sequenceNumber"].as_u64());
+        let total = rapporter.len() as u32;
+        let items = rapporter
+            .into_iter()
+            .skip(((page - 1) * size) as usize)
+            .take(size as usize)
+            .collect::<Vec<_>>();
+        return Svar::json(&json!({
+            "metaInformation": {
+                "currentPage": page,
+                "totalPages": total.div_ceil(size),
+                "totalResources": total,
+            },
+            "items": items,
+        }));
+    }
+
+    let delar = rest.trim_start_matches('/').split('/').collect::<Vec<_>>();
+    let id = match delar[..] {
+        [store, register, nr] | [store, register, nr, "pdf"] => (store, register, nr),
+        _ => return Svar::status(404),
+    };
+    let rapport = state.rapporter.iter().find(|r| {
+        (r["storeNumber"].to_string(), r["cashRegisterNumber"].to_string(), r["sequenceNumber"].to_string())
+            == (id.0.to_string(), id.1.to_string(), id.2.to_string())
+    });
+    match (rapport, delar.len()) {
+        (None, _) => Svar::status(404),
+        (Some(rapport), 3) => Svar::json(rapport),
+        (Some(_), _) => Svar {
+            status: 200,
+            content_type: "application/pdf",
+            body: PDF.to_vec(),
+        },
+    }
+}
+
+impl FakeEasyCashier {
+    pub fn start(orgnr: &str, rapporter: Vec<Value>) -> Self {
+        let state = Arc::new(Mutex::new(State {
+            rapporter,
+            ..Default::default()
+        }));
+        let server = {
+            let state = state.clone();
+            let orgnr = orgnr.to_string();
+            FakeServer::start(move |anrop| hantera(&state, &orgnr, anrop))
+        };
+        FakeEasyCashier { server, state }
+    }
+}
@@ -0,0 +1,143 @@
+use super::{Katalog, ORGNR, intervall, las_in, titel};
+use super::easycashier_server::{FakeEasyCashier, zrapport};
+use super::fortnox_server::{self, FakeFortnox, verifikat};
+use crate::backend::LedgerSink;
+use crate::easycashier::EasyCashier;
+use crate::error::Error;
+use crate::fortnox::Fortnox;
+use crate::mapping::Mapping;
+use crate::orgnr::Orgnr;
+use crate::recording;
+use crate::{Kontroll, importera_rapport};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::time::Duration;
+
+#[test]
+fn importerar_verifikat_i_fortnox_och_kopplar_underlag() {
+    let katalog = Katalog::ny("fortnox");
+    let rapporter = vec![
+        zrapport(ORGNR, 7, 1, "2025-03-10T18:00:00", 125),
+        zrapport(ORGNR, 7, 2, "2025-03-11T18:00:00", 250),
+    ];
+    let bokford = titel(&rapporter[0]);
+    let easycashier = FakeEasyCashier::start(ORGNR, rapporter);
+    let server = FakeFortnox::start(
+        ORGNR,
+        vec![
+            verifikat(2, "A", 1, &bokford, "2025-03-10"),
+            // Andra serier och annan text räknas inte som Z-Rapporter
+            verifikat(2, "B", 1, "Z, Bu: 7 Ka: 1 Nr: 2 Kv: 1 - 2", "2025-03-11"),
+            verifikat(2, "A", 2, "Hyra mars", "2025-03-11"),
+        ],
+    );
+    let easy = EasyCashier::login(recording::agent(), &easycashier.server.url, "test", "hemligt", "").unwrap();
+    let mut fortnox = Fortnox::new(&server.server.url, fortnox_server::TOKEN);
+    fortnox.page_size = 1;
+    assert_eq!(fortnox.organization_number().unwrap(), ORGNR);
+
+    let date_req = intervall("2025-03-10", "2025-03-11");
+    let rapporter = las_in(&fortnox, "fortnox", &easy, &date_req);
+    assert_eq!(rapporter.len(), 2);
+    assert_eq!(rapporter[0].verifikat.as_ref().map(|e| e.id.as_str()), Some("2:A:1"));
+    assert!(rapporter[1].importerbar());
+    assert_eq!(server.state.lock().unwrap().sidor, [1, 2, 3]);
+
+    let kontroll = Kontroll {
+        bokio_orgnr: Some(ORGNR.parse::<Orgnr>().unwrap()),
+        kontoplan: fortnox.chart_of_accounts().unwrap(),
+    };
+    let mut out = Vec::new();
+    let entry = importera_rapport(&easy, &fortnox, &kontroll, &Mapping::default(), &rapporter[1], katalog.path(), &mut out)
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(String::from_utf8_lossy(&out).contains("OK"));
+    assert_eq!(entry.id, "2:A:3");
+    assert_eq!(entry.journal_entry_number.to_string(), "A3");
+    let debet = entry.items.iter().map(|i| i.debit).sum::<Decimal>();
+    assert_eq!(debet, rapporter[1].rapport.konto(1580));
+
+    {
+        let state = server.state.lock().unwrap();
+        assert_eq!(state.inkorg, [("file-1".to_string(), format!("Z-Rapport_{}_7-1-2.pdf", ORGNR))]);
+        assert_eq!(
+            state.kopplingar,
+            [("file-1".to_string(), "A".to_string(), "3".to_string(), 2)]
+        );
+    }
+
+    // En ny körning känner igen båda rapporterna som bokförda utan att hämta
+    // verifikaten ett och ett
+    let rapporter = las_in(&fortnox, "fortnox", &easy, &date_req);
+    assert!(rapporter.iter().all(|e| e.verifikat.is_some()));
+    assert!(server.state.lock().unwrap().hamtade.is_empty());
+
+    // Raderna hämtas bara för verifikat som ska visas
+    let verifikat = rapporter[1].verifikat.as_ref().unwrap();
+    assert!(verifikat.items.is_empty());
+    let verifikat = fortnox.entry(verifikat).unwrap();
+    assert_eq!(verifikat.items.iter().map(|i| i.debit).sum::<Decimal>(), debet);
+    assert_eq!(server.state.lock().unwrap().hamtade, ["A/3"]);
+}
+
+#[test]
+fn fortnox_rakenskapsar_har_last_period() {
+    let server = FakeFortnox::start(ORGNR, Vec::new());
+    let fortnox = Fortnox::new(&server.server.url, fortnox_server::TOKEN);
+    let years = fortnox.fiscal_years().unwrap();
+    assert_eq!(years.len(), 2);
+    assert!(years.iter().all(|fy| fy.locked_until == NaiveDate::from_ymd_opt(2025, 2, 28)));
+}
+
+#[test]
+fn anrop_gors_om_efter_for_manga_anrop() {
+    let server = FakeFortnox::start(ORGNR, vec![verifikat(2, "A", 1, "Z, Bu: 7 Ka: 1 Nr: 1 Kv: 1 - 1", "2025-03-10")]);
+    let mut fortnox = Fortnox::new(&server.server.url, fortnox_server::TOKEN);
+    fortnox.rate_limit_wait = Duration::ZERO;
+
+    server.state.lock().unwrap().for_manga_anrop = 3;
+    let entries = fortnox.list_entries(None, None, Some("Z, ")).unwrap();
+    assert_eq!(entries.len(), 1);
+
+    // Efter fem försök ges det upp
+    server.state.lock().unwrap().for_manga_anrop = 5;
+    assert!(matches!(
+        fortnox.organization_number(),
+        Err(Error::Http(ureq::Error::StatusCode(429)))
+    ));
+}
+
+#[test]
+fn underlag_som_saknas_ger_fel() {
+    let katalog = Katalog::ny("fortnox-underlag");
+    let server = FakeFortnox::start(ORGNR, vec![verifikat(2, "A", 1, "Z, Bu: 7 Ka: 1 Nr: 1 Kv: 1 - 1", "2025-03-10")]);
+    let fortnox = Fortnox::new(&server.server.url, fortnox_server::TOKEN);
+    let entry = fortnox.list_entries(None, None, None).unwrap().remove(0);
+    let filename = format!("{}/saknas.pdf", katalog.path());
+    let err = fortnox.attach_file(&filename, "application/pdf", &entry).unwrap_err();
+    assert!(matches!(err, Error::Other(msg) if msg.starts_with(&format!("Kunde inte öppna {}", filename))));
+    assert!(server.state.lock().unwrap().inkorg.is_empty());
+}
+
+/// Fortnox API säger inte vilka verifikat som rättats, så en rapport med ett
+/// rättat verifikat räknas som bokförd och importeras inte igen.
+#[test]
+fn rattade_verifikat_raknas_som_bokforda() {
+    let rapporter = vec![zrapport(ORGNR, 7, 1, "2025-03-10T18:00:00", 125)];
+    let bokford = titel(&rapporter[0]);
+    let easycashier = FakeEasyCashier::start(ORGNR, rapporter);
+    let server = FakeFortnox::start(
+        ORGNR,
+        vec![
+            verifikat(2, "A", 1, &bokford, "2025-03-10"),
+            verifikat(2, "A", 2, &format!("Korrigering av A1, {}", bokford), "2025-03-10"),
+        ],
+    );
+    let easy = EasyCashier::login(recording::agent(), &easycashier.server.url, "test", "hemligt", "").unwrap();
+    let fortnox = Fortnox::new(&server.server.url, fortnox_server::TOKEN);
+
+    let rapporter = las_in(&fortnox, "fortnox", &easy, &intervall("2025-03-10", "2025-03-10"));
+    let verifikat = rapporter[0].verifikat.as_ref().unwrap();
+    assert_eq!(verifikat.id, "2:A:1");
+    assert_eq!(verifikat.reversed_by_journal_entry_id, None);
+    assert!(!rapporter[0].importerbar());
+}
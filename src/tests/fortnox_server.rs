@@ -0,0 +1,196 @@
+use super::{Anrop, FakeServer, Svar};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex};
+
+pub const TOKEN: &str = "fortnox-token";
+
+/// Räkenskapsår i servern, `(id, från, till)`.
+const RAKENSKAPSAR: [(u32, &str, &str); 2] = [(1, "2024-01-01", "2024-12-31"), (2, "2025-01-01", "2025-12-31")];
+
+#[derive(Default)]
+pub struct State {
+    /// Verifikat i Fortnox JSON-format med `Year`, i den ordning de skapades.
+    pub verifikat: Vec<Value>,
+    /// Filer i inkorgen för verifikat, `(fil-id, filnamn)`.
+    pub inkorg: Vec<(String, String)>,
+    /// Kopplingar mellan fil och verifikat, `(fil-id, serie, nummer, räkenskapsår)`.
+    pub kopplingar: Vec<(String, String, String, u32)>,
+    /// Sidor som hämtats från listningen av verifikat.
+    pub sidor: Vec<u32>,
+    /// Verifikat som hämtats ett och ett, `SERIE/NUMMER`.
+    pub hamtade: Vec<String>,
+    /// Antal kommande anrop som får 429 Too Many Requests.
+    pub for_manga_anrop: u32,
+}
+
+/// Låtsasserver för Fortnox med företag, räkenskapsår, låst period,
+/// kontoplan, verifikat, inkorg och filkopplingar.
+pub struct FakeFortnox {
+    pub server: FakeServer,
+    pub state: Arc<Mutex<State>>,
+}
+
+/// Verifikat som det ser ut i Fortnox.
+pub fn verifikat(year: u32, series: &str, nummer: u32, description: &str, date: &str) -> Value {
+    json!({
+        "Description": description,
+        "TransactionDate": date,
+        "VoucherSeries": series,
+        "VoucherNumber": nummer,
+        "Year": year,
+        "VoucherRows": [],
+    })
+}
+
+fn rakenskapsar(date: &str) -> Option<u32> {
+    RAKENSKAPSAR
+        .iter()
+        .find(|(_, from, to)| *from <= date && date <= *to)
+        .map(|(id, _, _)| *id)
+}
+
+fn hantera(state: &Mutex<State>, orgnr: &str, anrop: Anrop) -> Svar {
+    if anrop.headers.get("authorization") != Some(&format!("Bearer {}", TOKEN)) {
+        return Svar::status(401);
+    }
+    let mut state = state.lock().unwrap();
+    if state.for_manga_anrop > 0 {
+        state.for_manga_anrop -= 1;
+        return Svar::status(429);
+    }
+    let year = anrop.query.get("financialyear").and_then(|y| y.parse::<u32>().ok());
+    let segment = anrop.path.split('/').skip(1).collect::<Vec<_>>();
+
+    match (anrop.method.as_str(), segment.as_slice()) {
+        ("GET", ["companyinformation"]) => Svar::json(&json!({
+            "CompanyInformation": { "CompanyName": "Testbolaget AB", "OrganizationNumber": orgnr },
+        })),
+        ("GET", ["financialyears"]) => Svar::json(&json!({
+            "FinancialYears": RAKENSKAPSAR
+                .iter()
+                .map(|(id, from, to)| json!({ "Id": id, "FromDate": from, "ToDate": to }))
+                .collect::<Vec<_>>(),
+        })),
+        ("GET", ["settings", "lockedperiod"]) => Svar::json(&json!({
+            "LockedPeriod": { "EndDate": "2025-02-28" },
+        })),
+        ("GET", ["accounts"]) => Svar::json(&json!({
+            "MetaInformation": { "@TotalResources": 4, "@TotalPages": 1, "@CurrentPage": 1 },
+            "Accounts": [
+                { "Number": 1580, "Description": "Fordringar för kontokort", "Active": true },
+                { "Number": 1911, "Description": "Kassa", "Active": true },
+                { "Number": 2611, "Description": "Utgående moms 25%", "Active": true },
+                { "Number": 3001, "Description": "Försäljning 25%", "Active": true },
+            ],
+        })),
+        ("GET", ["vouchers"]) => {
+            let page = anrop.query["page"].parse::<u32>().unwrap();
+            let limit = anrop.query["limit"].parse::<u32>().unwrap();
+            state.sidor.push(page);
+            let from = anrop.query.get("fromdate").map(String::as_str).unwrap_or("");
+            let to = anrop.query.get("todate").map(String::as_str).unwrap_or("9999");
+            let verifikat = state
+                .verifikat
+                .iter()
+                .filter(|v| Some(v["Year"].as_u64().unwrap() as u32) == year)
+                .filter(|v| {
+                    let date = v["TransactionDate"].as_str().unwrap();
+                    from <= date && date <= to
+                })
+                .map(|v| {
+                    // Listningen innehåller inte raderna
+                    let mut v = v.clone();
+                    v.as_object_mut().unwrap().remove("VoucherRows");
+                    v
+                })
+                .collect::<Vec<_>>();
+            let total = verifikat.len() as u32;
+            let vouchers = verifikat
+                .into_iter()
+                .skip(((page - 1) * limit) as usize)
+                .take(limit as usize)
+                .collect::<Vec<_>>();
+            Svar::json(&json!({
+                "MetaInformation": {
+                    "@TotalResources": total,
+                    "@TotalPages": total.div_ceil(limit).max(1),
+                    "@CurrentPage": page,
+                },
+                "Vouchers": vouchers,
+            }))
+        }
+        ("GET", ["vouchers", series, nummer]) => {
+            state.hamtade.push(format!("{}/{}", series, nummer));
+            state
+                .verifikat
+                .iter()
+                .find(|v| {
+                    v["VoucherSeries"] == *series
+                        && nummer.parse::<u64>().ok() == v["VoucherNumber"].as_u64()
+                        && Some(v["Year"].as_u64().unwrap() as u32) == year
+                })
+                .map_or(Svar::status(404), |v| Svar::json(&json!({ "Voucher": v })))
+        }
+        ("POST", ["vouchers"]) => {
+            let mut voucher = serde_json::from_slice::<Value>(&anrop.body).unwrap()["Voucher"].clone();
+            let date = voucher["TransactionDate"].as_str().unwrap();
+            if rakenskapsar(date) != year {
+                return Svar::status(400);
+            }
+            let series = voucher["VoucherSeries"].clone();
+            let nummer = state
+                .verifikat
+                .iter()
+                .filter(|v| v["VoucherSeries"] == series && Some(v["Year"].as_u64().unwrap() as u32) == year)
+                .count()
+                + 1;
+            voucher["VoucherNumber"] = json!(nummer);
+            voucher["Year"] = json!(year);
+            state.verifikat.push(voucher.clone());
+            Svar::json(&json!({ "Voucher": voucher }))
+        }
+        ("POST", ["inbox"]) => {
+            assert_eq!(anrop.query.get("path").map(String::as_str), Some("inbox_v"));
+            let body = String::from_utf8_lossy(&anrop.body);
+            let filename = body
+                .split("filename=\"")
+                .nth(1)
+                .and_then(|s| s.split('"').next())
+                .unwrap_or_default()
+                .to_string();
+            let id = format!("file-{}", state.inkorg.len() + 1);
+            state.inkorg.push((id.clone(), filename.clone()));
+            Svar::json(&json!({ "File": { "Id": id, "Name": filename } }))
+        }
+        ("POST", ["voucherfileconnections"]) => {
+            let connection = serde_json::from_slice::<Value>(&anrop.body).unwrap()["VoucherFileConnection"].clone();
+            let file_id = connection["FileId"].as_str().unwrap().to_string();
+            if !state.inkorg.iter().any(|(id, _)| *id == file_id) {
+                return Svar::status(400);
+            }
+            state.kopplingar.push((
+                file_id,
+                connection["VoucherSeries"].as_str().unwrap().to_string(),
+                connection["VoucherNumber"].as_str().unwrap().to_string(),
+                year.unwrap(),
+            ));
+            Svar::json(&json!({ "VoucherFileConnection": connection }))
+        }
+        _ => Svar::status(404),
+    }
+}
+
+impl FakeFortnox {
+    pub fn start(orgnr: &str, verifikat: Vec<Value>) -> Self {
+        let state = Arc::new(Mutex::new(State {
+            verifikat,
+            ..Default::default()
+        }));
+        let server = {
+            let state = state.clone();
+            let orgnr = orgnr.to_string();
+            FakeServer::start(move |anrop| hantera(&state, &orgnr, anrop))
+        };
+        FakeFortnox { server, state }
+    }
+}
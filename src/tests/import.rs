@@ -0,0 +1,298 @@
+use super::{Katalog, ORGNR, datum, intervall, las_in, titel};
+use super::bokio_server::{self, FakeBokio, verifikat};
+use super::easycashier_server::{self, FakeEasyCashier, zrapport};
+use crate::backend::ZReportSource;
+use crate::bokio::Bokio;
+use crate::easycashier::EasyCashier;
+use crate::error::Error;
+use crate::journal_cache::JournalCache;
+use crate::ledger::JournalEntryNumber;
+use crate::mapping::Mapping;
+use crate::orgnr::Orgnr;
+use crate::recording;
+use crate::{Kontroll, importera_rapport};
+use chrono::Days;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+struct Miljo {
+    easycashier: FakeEasyCashier,
+    bokio_server: FakeBokio,
+    easy: EasyCashier,
+    bokio: Bokio,
+}
+
+fn starta(rapporter: Vec<Value>, verifikat: Vec<Value>) -> Miljo {
+    let easycashier = FakeEasyCashier::start(ORGNR, rapporter);
+    let bokio_server = FakeBokio::start(ORGNR, verifikat);
+    let easy = EasyCashier::login(recording::agent(), &easycashier.server.url, "test", "hemligt", "").unwrap();
+    assert_eq!(easy.company, ORGNR);
+    let bokio = Bokio::new(&bokio_server.server.url, bokio_server::COMPANY_ID, bokio_server::TOKEN);
+    Miljo {
+        easycashier,
+        bokio_server,
+        easy,
+        bokio,
+    }
+}
+
+fn kontroll(bokio: &Bokio) -> Kontroll {
+    Kontroll {
+        bokio_orgnr: Some(ORGNR.parse::<Orgnr>().unwrap()),
+        kontoplan: bokio.chart_of_accounts().unwrap(),
+    }
+}
+
+#[test]
+fn hamtar_alla_sidor_fran_easycashier() {
+    for (antal, sidor) in [(0, 1), (1, 1), (100, 1), (101, 2), (200, 2), (201, 3)] {
+        let rapporter = (1..=antal)
+            .map(|nr| zrapport(ORGNR, 1, nr, "2025-03-10T18:00:00", 100))
+            .collect();
+        let m = starta(rapporter, Vec::new());
+
+        let lst = m.easy.list_reports(&intervall("2025-03-10", "2025-03-10")).unwrap();
+        let nummer = lst.iter().map(|r| r.sequence_number).collect::<Vec<_>>();
+        assert_eq!(nummer, (1..=antal).collect::<Vec<_>>(), "{} rapporter", antal);
+        assert_eq!(m.easycashier.state.lock().unwrap().sidor.len(), sidor, "{} rapporter", antal);
+    }
+}
+
+#[test]
+fn hamtar_bara_rapporter_inom_datumintervallet() {
+    let rapporter = vec![
+        zrapport(ORGNR, 1, 1, "2025-03-09T18:00:00", 100),
+        zrapport(ORGNR, 1, 2, "2025-03-10T18:00:00", 100),
+        zrapport(ORGNR, 1, 3, "2025-03-11T18:00:00", 100),
+    ];
+    let m = starta(rapporter, Vec::new());
+
+    let lst = m.easy.list_reports(&intervall("2025-03-10", "2025-03-11")).unwrap();
+    assert_eq!(lst.iter().map(|r| r.sequence_number).collect::<Vec<_>>(), [2, 3]);
+}
+
+#[test]
+fn hittar_bokforda_rapporter_pa_senare_sidor_i_bokio() {
+    for antal_andra in [3, 4, 5] {
+        let rapport = zrapport(ORGNR, 2, 7, "2025-03-10T18:00:00", 100);
+        // Verifikatet för rapporten är äldst och hamnar på sista sidan
+        let mut bokforda = vec![verifikat("je-z", 1, &titel(&rapport), "2025-03-10")];
+        for i in 0..antal_andra {
+            bokforda.push(verifikat(
+                &format!("je-{}", i),
+                i + 2,
+                &format!("Z, Bu: 9 Ka: 1 Nr: {} Kv: 1 - 2", i),
+                "2025-03-12",
+            ));
+        }
+        let mut m = starta(vec![rapport], bokforda);
+        m.bokio.page_size = 2;
+
+        let rapporter = las_in(&m.bokio, bokio_server::COMPANY_ID, &m.easy, &intervall("2025-03-10", "2025-03-12"));
+        assert_eq!(rapporter.len(), 1);
+        let verifikat = rapporter[0].verifikat.as_ref().expect("rapporten är bokförd");
+        assert_eq!(verifikat.id, "je-z");
+        assert!(!rapporter[0].importerbar());
+
+        let sidor = m.bokio_server.state.lock().unwrap().sidor.clone();
+        let forvantat = (antal_andra + 1).div_ceil(2);
+        assert_eq!(sidor, (1..=forvantat).collect::<Vec<_>>(), "{} verifikat", antal_andra + 1);
+    }
+}
+
+#[test]
+fn synkning_hamtar_bara_verifikat_efter_forra_synkningen() {
+    let katalog = Katalog::ny("journal-cache");
+    std::fs::create_dir_all(katalog.path()).unwrap();
+    let fil = format!("{}/cache.json", katalog.path());
+    let idag = chrono::Utc::now().date_naive();
+    let dagar_sedan = |n: u64| (idag - Days::new(n)).format("%Y-%m-%d").to_string();
+    let m = starta(
+        Vec::new(),
+        vec![
+            verifikat("je-1", 1, "Z, Bu: 1 Ka: 1 Nr: 1 Kv: 1 - 2", &dagar_sedan(40)),
+            verifikat("je-2", 2, "Z, Bu: 1 Ka: 1 Nr: 2 Kv: 1 - 2", &dagar_sedan(2)),
+        ],
+    );
+    let synka = |refresh: bool| {
+        let bokio = Bokio::new(&m.bokio_server.server.url, bokio_server::COMPANY_ID, bokio_server::TOKEN);
+        let cache =
+            JournalCache::sync(&fil, &bokio, bokio_server::COMPANY_ID, "Z, ", datum("2025-01-01"), refresh).unwrap();
+        cache.save(&fil).unwrap();
+        let mut ids = cache.entries.iter().map(|e| e.id.clone()).collect::<Vec<_>>();
+        ids.sort();
+        let filter = m.bokio_server.state.lock().unwrap().filter.drain(..).collect::<Vec<_>>();
+        (ids, filter)
+    };
+    let (ids, filter) = synka(false);
+    assert_eq!(ids, ["je-1", "je-2"]);
+    assert!(filter.iter().all(|f| f.starts_with("date>=2025-01-01&&")), "{:?}", filter);
+
+    // Ett verifikat bokförs i efterhand, ett långt tillbaka och ett tas bort
+    {
+        let mut state = m.bokio_server.state.lock().unwrap();
+        state.verifikat.push(verifikat("je-3", 3, "Z, Bu: 1 Ka: 1 Nr: 3 Kv: 1 - 2", &dagar_sedan(5)));
+        state.verifikat.push(verifikat("je-4", 4, "Z, Bu: 1 Ka: 1 Nr: 4 Kv: 1 - 2", &dagar_sedan(30)));
+        state.verifikat.retain(|v| v["id"] != "je-2");
+    }
+    // Nästa synkning hämtar bara från förra synkningen minus marginalen
+    let (ids, filter) = synka(false);
+    assert_eq!(ids, ["je-1", "je-3"]);
+    let fran = format!("date>={}&&", dagar_sedan(crate::journal_cache::SYNC_MARGIN_DAYS));
+    assert!(!filter.is_empty() && filter.iter().all(|f| f.starts_with(&fran)), "{:?}", filter);
+
+    // Med --refresh byggs hela cachen om
+    let (ids, filter) = synka(true);
+    assert_eq!(ids, ["je-1", "je-3", "je-4"]);
+    assert!(filter.iter().all(|f| f.starts_with("date>=2025-01-01&&")), "{:?}", filter);
+}
+
+#[test]
+fn verifikat_som_inte_kan_tolkas_stoppar_listningen() {
+    let mut trasigt = verifikat("je-2", 2, "Z, Bu: 1 Ka: 1 Nr: 2 Kv: 1 - 2", "2025-03-10");
+    trasigt["items"] = Value::Null;
+    let m = starta(
+        Vec::new(),
+        vec![verifikat("je-1", 1, "Z, Bu: 1 Ka: 1 Nr: 1 Kv: 1 - 2", "2025-03-10"), trasigt],
+    );
+
+    let err = m.bokio.list_journal(Some(datum("2025-03-01")), None, Some("Z, ")).unwrap_err();
+    assert!(err.to_string().contains("verifikat je-2"), "{}", err);
+}
+
+#[test]
+fn verifikatnummer_med_serie_och_ar() {
+    for (text, serie, nummer) in [("V123", "V", 123), ("V-2024-12", "V-2024", 12), ("A 7", "A", 7)] {
+        let nr = text.parse::<JournalEntryNumber>().unwrap();
+        assert_eq!((nr.series.as_str(), nr.number), (serie, nummer), "{}", text);
+        assert_eq!(nr.to_string(), text);
+    }
+    assert!("V-2024-".parse::<JournalEntryNumber>().is_err());
+}
+
+#[test]
+fn importerar_rapporter_och_laddar_upp_underlag() {
+    let katalog = Katalog::ny("import");
+    let rapporter = vec![
+        zrapport(ORGNR, 3, 1, "2025-03-10T18:00:00", 125),
+        zrapport(ORGNR, 3, 2, "2025-03-11T18:00:00", 250),
+        zrapport(ORGNR, 3, 3, "2025-03-12T18:00:00", 500),
+    ];
+    let bokford = verifikat("je-0", 1, &titel(&rapporter[0]), "2025-03-10");
+    let m = starta(rapporter, vec![bokford]);
+    let date_req = intervall("2025-03-10", "2025-03-12");
+
+    let rapporter = las_in(&m.bokio, bokio_server::COMPANY_ID, &m.easy, &date_req);
+    let importerbara = rapporter.iter().filter(|e| e.importerbar()).collect::<Vec<_>>();
+    assert_eq!(
+        importerbara.iter().map(|e| e.rapport.sequence_number).collect::<Vec<_>>(),
+        [2, 3]
+    );
+
+    let kontroll = kontroll(&m.bokio);
+    for e in importerbara {
+        let mut out = Vec::new();
+        let entry = importera_rapport(&m.easy, &m.bokio, &kontroll, &Mapping::default(), e, katalog.path(), &mut out)
+            .unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(entry.title, e.titel);
+        assert_eq!(Some(entry.date), e.datum);
+        let debet = entry.items.iter().map(|i| i.debit).sum::<Decimal>();
+        let kredit = entry.items.iter().map(|i| i.credit).sum::<Decimal>();
+        assert_eq!(debet, kredit);
+        assert_eq!(debet, e.rapport.konto(1580));
+        assert!(String::from_utf8_lossy(&out).contains("OK"));
+    }
+
+    {
+        let state = m.bokio_server.state.lock().unwrap();
+        assert_eq!(state.verifikat.len(), 3);
+        assert_eq!(
+            state.underlag,
+            [
+                ("je-2".to_string(), format!("Z-Rapport_{}_3-1-2.pdf", ORGNR)),
+                ("je-3".to_string(), format!("Z-Rapport_{}_3-1-3.pdf", ORGNR)),
+            ]
+        );
+    }
+    let pdf = std::fs::read(format!("{}/Z-Rapport_{}_3-1-2.pdf", katalog.path(), ORGNR)).unwrap();
+    assert_eq!(pdf, easycashier_server::PDF);
+
+    // En ny körning känner igen alla rapporter som bokförda
+    let bokio = Bokio::new(&m.bokio_server.server.url, bokio_server::COMPANY_ID, bokio_server::TOKEN);
+    let rapporter = las_in(&bokio, bokio_server::COMPANY_ID, &m.easy, &date_req);
+    assert!(rapporter.iter().all(|e| e.verifikat.is_some()));
+}
+
+#[test]
+fn vant_verifikat_raknas_inte_som_bokfort() {
+    let rapport = zrapport(ORGNR, 4, 1, "2025-03-10T18:00:00", 100);
+    let mut original = verifikat("je-1", 1, &titel(&rapport), "2025-03-10");
+    original["reversedByJournalEntryId"] = "je-2".into();
+    let mut vandning = verifikat("je-2", 2, "Vändning av V1", "2025-03-11");
+    vandning["reversingJournalEntryId"] = "je-1".into();
+    let m = starta(vec![rapport], vec![original, vandning]);
+
+    let rapporter = las_in(&m.bokio, bokio_server::COMPANY_ID, &m.easy, &intervall("2025-03-10", "2025-03-11"));
+    assert_eq!(rapporter.len(), 1);
+    assert!(rapporter[0].importerbar());
+}
+
+#[test]
+fn misslyckad_uppladdning_ger_verifikat_utan_underlag() {
+    let katalog = Katalog::ny("import-uppladdning");
+    let m = starta(vec![zrapport(ORGNR, 5, 1, "2025-03-10T18:00:00", 100)], Vec::new());
+    m.bokio_server.state.lock().unwrap().fel_vid_uppladdning = true;
+
+    let rapporter = las_in(&m.bokio, bokio_server::COMPANY_ID, &m.easy, &intervall("2025-03-10", "2025-03-10"));
+    let mut out = Vec::new();
+    let entry = importera_rapport(
+        &m.easy,
+        &m.bokio,
+        &kontroll(&m.bokio),
+        &Mapping::default(),
+        &rapporter[0],
+        katalog.path(),
+        &mut out,
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(entry.journal_entry_number.to_string(), "V1");
+    assert!(String::from_utf8_lossy(&out).contains("Misslyckades"));
+    let state = m.bokio_server.state.lock().unwrap();
+    assert_eq!(state.verifikat.len(), 1);
+    assert!(state.underlag.is_empty());
+}
+
+#[test]
+fn misslyckad_bokforing_ger_fel() {
+    let katalog = Katalog::ny("import-bokforing");
+    let m = starta(vec![zrapport(ORGNR, 6, 1, "2025-03-10T18:00:00", 100)], Vec::new());
+    m.bokio_server.state.lock().unwrap().fel_vid_bokforing = true;
+
+    let rapporter = las_in(&m.bokio, bokio_server::COMPANY_ID, &m.easy, &intervall("2025-03-10", "2025-03-10"));
+    let err = importera_rapport(
+        &m.easy,
+        &m.bokio,
+        &kontroll(&m.bokio),
+        &Mapping::default(),
+        &rapporter[0],
+        katalog.path(),
+        &mut Vec::new(),
+    )
+    .unwrap_err();
+
+    assert!(err.contains("Kunde inte bokföra verifikat för Z-Rapport 1"), "{}", err);
+    let state = m.bokio_server.state.lock().unwrap();
+    assert!(state.verifikat.is_empty());
+    assert!(state.underlag.is_empty());
+}
+
+#[test]
+fn underlag_som_saknas_ger_fel() {
+    let katalog = Katalog::ny("bokio-underlag");
+    let m = starta(Vec::new(), Vec::new());
+    let filename = format!("{}/saknas.pdf", katalog.path());
+    let err = m.bokio.upload(&filename, "application/pdf", "je-1").unwrap_err();
+    assert!(matches!(err, Error::Other(msg) if msg.starts_with(&format!("Kunde inte öppna {}", filename))));
+    assert!(m.bokio_server.state.lock().unwrap().underlag.is_empty());
+}
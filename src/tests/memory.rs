@@ -0,0 +1,176 @@
+use super::{Katalog, ORGNR, rapport};
+use crate::backend::{LedgerSink, ZReportSource};
+use crate::error::Error;
+use crate::journal_cache::JournalCache;
+use crate::ledger::{
+    Account, CreateJournal, FiscalYear, JournalEntry, JournalEntryAccount, JournalEntryNumber,
+};
+use crate::mapping::Mapping;
+use crate::orgnr::Orgnr;
+use crate::title::TitleTemplate;
+use crate::zrapport::{DateRequest, ZRapport, ZRapportId};
+use crate::{Kontroll, hamta_rapporter, importera_rapport};
+use chrono::NaiveDate;
+use std::cell::RefCell;
+
+/// Kassasystem i minnet. Underlaget sparas i en temporär katalog.
+struct MemorySource {
+    rapporter: Vec<ZRapport>,
+    katalog: std::path::PathBuf,
+}
+
+impl ZReportSource for MemorySource {
+    fn company(&self) -> &str {
+        ORGNR
+    }
+
+    fn list_reports(&self, date_req: &DateRequest) -> Result<Vec<ZRapport>, Error> {
+        Ok(self
+            .rapporter
+            .iter()
+            .filter(|r| {
+                let datum = r.datum(0).unwrap();
+                date_req.start_date <= datum && datum <= date_req.end_date
+            })
+            .map(|r| serde_json::from_value(serde_json::to_value(r).unwrap()).unwrap())
+            .collect())
+    }
+
+    fn report(&self, id: &ZRapportId) -> Result<ZRapport, Error> {
+        self.rapporter
+            .iter()
+            .find(|r| r.id().to_string() == id.to_string())
+            .map(|r| serde_json::from_value(serde_json::to_value(r).unwrap()).unwrap())
+            .ok_or_else(|| Error::Other(format!("Z-Rapport {} saknas", id)))
+    }
+
+    fn document(&self, rapport: &ZRapport) -> Result<(Vec<u8>, String), Error> {
+        let filename = self.katalog.join(format!("Z-Rapport_{}.pdf", rapport.sequence_number));
+        Ok((b"%PDF".to_vec(), filename.to_str().unwrap().to_string()))
+    }
+}
+
+/// Bokföring i minnet.
+#[derive(Default)]
+struct MemoryLedger {
+    verifikat: RefCell<Vec<JournalEntry>>,
+    underlag: RefCell<Vec<(String, String)>>,
+}
+
+impl LedgerSink for MemoryLedger {
+    fn name(&self) -> &str {
+        "Minne"
+    }
+
+    fn organization_number(&self) -> Result<String, Error> {
+        Ok(ORGNR.to_string())
+    }
+
+    fn fiscal_years(&self) -> Result<Vec<FiscalYear>, Error> {
+        Ok(vec![FiscalYear {
+            id: "fy".to_string(),
+            start_date: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2025, 12, 31).unwrap(),
+            status: "open".to_string(),
+            locked_until: NaiveDate::from_ymd_opt(2025, 2, 28),
+        }])
+    }
+
+    fn chart_of_accounts(&self) -> Result<Vec<Account>, Error> {
+        Ok([1580, 2611, 3001]
+            .into_iter()
+            .map(|account| Account {
+                account,
+                name: account.to_string(),
+                is_active: true,
+            })
+            .collect())
+    }
+
+    fn list_entries(
+        &self,
+        start_date: Option<NaiveDate>,
+        end_date: Option<NaiveDate>,
+        title_prefix: Option<&str>,
+    ) -> Result<Vec<JournalEntry>, Error> {
+        Ok(self
+            .verifikat
+            .borrow()
+            .iter()
+            .filter(|e| start_date.is_none_or(|d| e.date >= d))
+            .filter(|e| end_date.is_none_or(|d| e.date <= d))
+            .filter(|e| title_prefix.is_none_or(|p| e.title.starts_with(p)))
+            .cloned()
+            .collect())
+    }
+
+    fn create_entry(&self, entry: &CreateJournal) -> Result<JournalEntry, Error> {
+        let mut verifikat = self.verifikat.borrow_mut();
+        let nummer = verifikat.len() + 1;
+        let entry = JournalEntry {
+            id: format!("je-{}", nummer),
+            title: entry.title.clone(),
+            journal_entry_number: JournalEntryNumber::try_from(format!("M{}", nummer)).unwrap(),
+            date: entry.date,
+            items: entry
+                .items
+                .iter()
+                .zip(1..)
+                .map(|(item, id)| JournalEntryAccount {
+                    id,
+                    account: item.account,
+                    debit: item.debit,
+                    credit: item.credit,
+                    description: item.description.clone(),
+                })
+                .collect(),
+            reversing_journal_entry_id: None,
+            reversed_by_journal_entry_id: None,
+        };
+        verifikat.push(entry.clone());
+        Ok(entry)
+    }
+
+    fn attach_file(&self, filename: &str, _content_type: &str, entry: &JournalEntry) -> Result<(), Error> {
+        assert!(std::path::Path::new(filename).exists());
+        self.underlag.borrow_mut().push((entry.id.clone(), filename.to_string()));
+        Ok(())
+    }
+}
+
+#[test]
+fn importerar_med_implementationer_i_minnet() {
+    let katalog = Katalog::ny("memory");
+    std::fs::create_dir_all(katalog.path()).unwrap();
+    let rapporter = [(1, "2025-02-28T18:00:00"), (2, "2025-03-01T18:00:00")]
+        .into_iter()
+        .map(|(nr, datum)| rapport(1, nr, datum, 100))
+        .collect();
+    let source = MemorySource {
+        rapporter,
+        katalog: katalog.path().into(),
+    };
+    let ledger = MemoryLedger::default();
+
+    let template = TitleTemplate::legacy();
+    let date_req = DateRequest::new(&NaiveDate::from_ymd_opt(2025, 2, 28), &NaiveDate::from_ymd_opt(2025, 3, 1));
+    let zrapporter = source.list_reports(&date_req).unwrap();
+    let cache = JournalCache::sync("", &ledger, "minne", "Z, ", date_req.start_date, true).unwrap();
+    let company = ORGNR.parse::<Orgnr>().unwrap();
+    let importer = hamta_rapporter(0, &date_req, &template, &company, &ledger, &cache, zrapporter).unwrap();
+
+    // Rapport 1 ligger i en låst period
+    assert!(importer[0].stangd.as_ref().is_some_and(|s| s.contains("låst")));
+    assert!(importer[1].importerbar());
+
+    let kontroll = Kontroll {
+        bokio_orgnr: Some(company),
+        kontoplan: ledger.chart_of_accounts().unwrap(),
+    };
+    let entry = importera_rapport(&source, &ledger, &kontroll, &Mapping::default(), &importer[1], "", &mut Vec::new())
+        .unwrap_or_else(|err| panic!("{}", err));
+
+    assert_eq!(entry.journal_entry_number.to_string(), "M1");
+    assert_eq!(ledger.underlag.borrow().len(), 1);
+    assert!(std::path::Path::new(katalog.path()).join("Z-Rapport_2_minne.json").exists());
+}
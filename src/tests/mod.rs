@@ -0,0 +1,226 @@
+//! Tester mot låtsasservrar för EasyCashier, Bokio och Fortnox som körs i processen.
+
+mod amount;
+mod bokio_server;
+mod easycashier_server;
+mod fortnox;
+mod fortnox_server;
+mod import;
+mod memory;
+mod orgnr;
+mod recording;
+mod schema;
+mod sequence;
+mod settlement;
+mod title;
+mod val;
+
+use crate::backend::{LedgerSink, ZReportSource};
+use crate::easycashier::EasyCashier;
+use crate::journal_cache::JournalCache;
+use crate::orgnr::Orgnr;
+use crate::title::TitleTemplate;
+use crate::zrapport::{DateRequest, ZRapport};
+use crate::{RapportImport, hamta_rapporter, titelprefix};
+use chrono::NaiveDate;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tiny_http::{Header, Response, Server};
+
+/// Ett HTTP-anrop till en låtsasserver.
+pub struct Anrop {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Svar från en låtsasserver: status, content type och innehåll.
+pub struct Svar {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Svar {
+    pub fn json(value: &serde_json::Value) -> Self {
+        Svar {
+            status: 200,
+            content_type: "application/json",
+            body: serde_json::to_vec(value).unwrap(),
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Svar {
+            status,
+            content_type: "text/plain",
+            body: Vec::new(),
+        }
+    }
+}
+
+fn avkoda(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => result.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+                result.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 2;
+            }
+            b => result.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(result).unwrap()
+}
+
+/// HTTP-server på en ledig port som svarar med `handler` tills den släpps.
+pub struct FakeServer {
+    pub url: String,
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FakeServer {
+    pub fn start(handler: impl Fn(Anrop) -> Svar + Send + 'static) -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let thread = {
+            let server = server.clone();
+            std::thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let (path, query) = request
+                        .url()
+                        .split_once('?')
+                        .map_or((request.url().to_string(), ""), |(p, q)| (p.to_string(), q));
+                    let query = query
+                        .split('&')
+                        .filter_map(|kv| kv.split_once('='))
+                        .map(|(k, v)| (avkoda(k), avkoda(v)))
+                        .collect();
+                    let headers = request
+                        .headers()
+                        .iter()
+                        .map(|h| (h.field.as_str().as_str().to_ascii_lowercase(), h.value.to_string()))
+                        .collect();
+                    let mut body = Vec::new();
+                    request.as_reader().read_to_end(&mut body).unwrap();
+                    let anrop = Anrop {
+                        method: request.method().as_str().to_string(),
+                        path,
+                        query,
+                        headers,
+                        body,
+                    };
+
+                    let svar = handler(anrop);
+                    let header = Header::from_bytes("Content-Type", svar.content_type).unwrap();
+                    let response = Response::from_data(svar.body)
+                        .with_status_code(svar.status)
+                        .with_header(header);
+                    request.respond(response).ok();
+                }
+            })
+        };
+
+        FakeServer {
+            url,
+            server,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Organisationsnummer för testföretaget i alla låtsasservrar.
+pub const ORGNR: &str = "5560360793";
+
+/// Z-Rapport för testföretaget, se `easycashier_server::zrapport`.
+pub fn rapport(store: u32, nr: u32, date_created: &str, kort: i64) -> ZRapport {
+    tolka_rapport(&easycashier_server::zrapport(ORGNR, store, nr, date_created, kort))
+}
+
+/// Z-Rapporten i EasyCashiers JSON-format som `ZRapport`.
+pub fn tolka_rapport(json: &Value) -> ZRapport {
+    serde_json::from_value(json.clone()).unwrap()
+}
+
+/// Verifikatnamn för en rapport med standardmallen.
+pub fn titel(json: &Value) -> String {
+    let rapport = tolka_rapport(json);
+    TitleTemplate::legacy().format(&rapport, rapport.datum(0).ok())
+}
+
+/// Tillfällig katalog som tas bort när testet är klart.
+pub struct Katalog(std::path::PathBuf);
+
+impl Katalog {
+    pub fn ny(namn: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("ecbokio-{}-{}", namn, std::process::id()));
+        std::fs::remove_dir_all(&path).ok();
+        Katalog(path)
+    }
+
+    pub fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+
+    pub fn innehall(&self) -> String {
+        let mut filer = std::fs::read_dir(&self.0)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect::<Vec<_>>();
+        filer.sort();
+        filer
+            .iter()
+            .map(|f| String::from_utf8_lossy(&std::fs::read(f).unwrap()).to_string())
+            .collect()
+    }
+}
+
+impl Drop for Katalog {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+pub fn datum(s: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+}
+
+pub fn intervall(start: &str, end: &str) -> DateRequest {
+    DateRequest::new(&Some(datum(start)), &Some(datum(end)))
+}
+
+/// Hämta rapporter och bokförda verifikat på samma sätt som `main`.
+pub fn las_in(ledger: &dyn LedgerSink, ledger_id: &str, easy: &EasyCashier, date_req: &DateRequest) -> Vec<RapportImport> {
+    let template = TitleTemplate::legacy();
+    let zrapporter = easy.list_reports(date_req).unwrap();
+    let cache = JournalCache::sync(
+        "",
+        ledger,
+        ledger_id,
+        &titelprefix(&template),
+        crate::bokio_startdatum(date_req),
+        true,
+    )
+    .unwrap();
+    let company = easy.company.parse::<Orgnr>().unwrap();
+    hamta_rapporter(0, date_req, &template, &company, ledger, &cache, zrapporter).unwrap()
+}
@@ -0,0 +1,43 @@
+use crate::orgnr::{Orgnr, OrgnrError};
+
+#[test]
+fn organisationsnummer_i_alla_former() {
+    for s in ["5560360793", "556036-0793", "165560360793", "16556036-0793", " 556036-0793 "] {
+        let orgnr = s.parse::<Orgnr>().unwrap_or_else(|err| panic!("{}", err));
+        assert_eq!(orgnr, "556036-0793".parse::<Orgnr>().unwrap(), "{}", s);
+    }
+    assert_eq!("5560360793".parse::<Orgnr>().unwrap().to_string(), "556036-0793");
+    assert_eq!("165560360793".parse::<Orgnr>().unwrap().to_string(), "16556036-0793");
+}
+
+#[test]
+fn ogiltiga_organisationsnummer_avvisas() {
+    assert_eq!(
+        "5560360794".parse::<Orgnr>().unwrap_err(),
+        OrgnrError::Checksum("5560360794".to_string())
+    );
+    for s in ["556036079", "55603607933", "556036-079", "55603-60793", "556036079X", ""] {
+        assert_eq!(s.parse::<Orgnr>().unwrap_err(), OrgnrError::Format(s.to_string()), "{}", s);
+    }
+    // Sekelprefix 16 gäller bara juridiska personer och 19/20 bara personnummer
+    assert_eq!(
+        "195560360793".parse::<Orgnr>().unwrap_err(),
+        OrgnrError::Century("195560360793".to_string())
+    );
+    assert_eq!(
+        "168112189876".parse::<Orgnr>().unwrap_err(),
+        OrgnrError::Century("168112189876".to_string())
+    );
+}
+
+#[test]
+fn personnummer_avvisas_om_det_inte_ar_tillatet() {
+    let orgnr = Orgnr::parse("19811218-9876", true).unwrap();
+    assert!(orgnr.is_personnummer());
+    assert_eq!(orgnr.to_string(), "19811218-9876");
+    assert_eq!(
+        Orgnr::parse("811218-9876", false).unwrap_err(),
+        OrgnrError::Personnummer("811218-9876".to_string())
+    );
+    assert!(!Orgnr::parse("556036-0793", false).unwrap().is_personnummer());
+}
@@ -0,0 +1,132 @@
+use super::{FakeServer, Katalog, ORGNR, Svar};
+use super::bokio_server::{self, FakeBokio};
+use super::easycashier_server::{self, FakeEasyCashier, zrapport};
+use crate::backend::ZReportSource;
+use crate::bokio::Bokio;
+use crate::easycashier::EasyCashier;
+use crate::error::Error;
+use crate::recording;
+use crate::zrapport::{DateRequest, ZRapportId};
+use chrono::NaiveDate;
+use ureq::Agent;
+
+fn intervall() -> DateRequest {
+    let datum = NaiveDate::from_ymd_opt(2025, 3, 10);
+    DateRequest::new(&datum, &datum)
+}
+
+/// Hämta rapporter, PDF och verifikat och returnera en sammanfattning
+/// som kan jämföras mellan inspelning och uppspelning.
+fn hamta(agent: Agent, easycashier_url: &str, bokio_url: &str, company_id: &str) -> (Vec<u32>, Vec<u8>, Vec<String>) {
+    let easy = EasyCashier::login(agent.clone(), easycashier_url, "kassa", "hemligt", "").unwrap();
+    let rapporter = easy.list_reports(&intervall()).unwrap();
+    let (pdf, _) = easy.zrapport_pdf(&rapporter[0]).unwrap();
+
+    let mut bokio = Bokio::new(bokio_url, company_id, bokio_server::TOKEN);
+    bokio.agent = agent;
+    let verifikat = bokio
+        .list_journal(None, None, None)
+        .unwrap()
+        .into_iter()
+        .map(|e| e.title)
+        .collect();
+    (rapporter.iter().map(|r| r.sequence_number).collect(), pdf, verifikat)
+}
+
+#[test]
+fn inspelning_maskerar_hemligheter_och_kan_spelas_upp() {
+    let katalog = Katalog::ny("record");
+    let inspelat = {
+        let easycashier = FakeEasyCashier::start(
+            ORGNR,
+            vec![
+                zrapport(ORGNR, 1, 1, "2025-03-10T12:00:00", 100),
+                zrapport(ORGNR, 1, 2, "2025-03-10T18:00:00", 200),
+            ],
+        );
+        let bokio = FakeBokio::start(
+            ORGNR,
+            vec![bokio_server::verifikat("je-1", 1, "Z, Bu: 1 Ka: 1 Nr: 0 Kv: 1 - 9", "2025-03-09")],
+        );
+        let agent = recording::agent_spela_in(katalog.path()).unwrap();
+        hamta(agent, &easycashier.server.url, &bokio.server.url, bokio_server::COMPANY_ID)
+    };
+    assert_eq!(inspelat.0, [1, 2]);
+    assert_eq!(inspelat.1, easycashier_server::PDF);
+
+    let innehall = katalog.innehall();
+    assert!(innehall.contains(recording::REDACTED));
+    for hemlighet in ["hemligt", "kassa", easycashier_server::TOKEN, bokio_server::TOKEN] {
+        assert!(!innehall.contains(hemlighet), "{} finns i inspelningen", hemlighet);
+    }
+
+    // Servrarna är stoppade, uppspelningen ska inte behöva någon server.
+    // Cachen av verifikat sparas i katalogen vid uppspelning och är ingen
+    // inspelning, och företagets id i Bokio behövs inte.
+    std::fs::write(format!("{}/ecbokio_journal_bokio_{}.json", katalog.path(), ORGNR), "{}").unwrap();
+    let agent = recording::agent_spela_upp(katalog.path()).unwrap();
+    let uppspelat = hamta(agent, "http://127.0.0.1:9", "http://127.0.0.1:9", recording::REDACTED);
+    assert_eq!(uppspelat, inspelat);
+}
+
+#[test]
+fn felsvar_spelas_in_och_upp_som_fel() {
+    let katalog = Katalog::ny("error");
+    {
+        let easycashier = FakeEasyCashier::start(ORGNR, Vec::new());
+        let agent = recording::agent_spela_in(katalog.path()).unwrap();
+        let easy = EasyCashier::login(agent, &easycashier.server.url, "kassa", "hemligt", "").unwrap();
+        let id = "1:1:99".parse::<ZRapportId>().unwrap();
+        let err = easy.zrapport(&id).err().unwrap();
+        assert!(matches!(err, Error::Http(ureq::Error::StatusCode(404))), "{}", err);
+    }
+
+    let agent = recording::agent_spela_upp(katalog.path()).unwrap();
+    let easy = EasyCashier::login(agent, "http://127.0.0.1:9", "", "", "").unwrap();
+    let id = "1:1:99".parse::<ZRapportId>().unwrap();
+    let err = easy.zrapport(&id).err().unwrap();
+    assert!(matches!(err, Error::Http(ureq::Error::StatusCode(404))), "{}", err);
+
+    // Varje inspelat anrop besvaras bara en gång
+    let err = easy.zrapport(&id).err().unwrap();
+    assert!(err.to_string().contains("ingen inspelning"), "{}", err);
+}
+
+#[test]
+fn parametrar_och_text_maskeras() {
+    let katalog = Katalog::ny("text");
+    {
+        let server = FakeServer::start(|_| Svar {
+            status: 200,
+            content_type: "text/plain",
+            body: b"access_token=hemligt&expires=3600\nToken: kassa\nOK".to_vec(),
+        });
+        let agent = recording::agent_spela_in(katalog.path()).unwrap();
+        let text = agent
+            .get(format!("{}/login?username=kassa&page=1", server.url))
+            .call()
+            .unwrap()
+            .body_mut()
+            .read_to_string()
+            .unwrap();
+        assert!(text.contains("hemligt"));
+    }
+
+    let innehall = katalog.innehall();
+    for hemlighet in ["hemligt", "kassa"] {
+        assert!(!innehall.contains(hemlighet), "{} finns i inspelningen: {}", hemlighet, innehall);
+    }
+    assert!(innehall.contains("page=1"));
+    assert!(innehall.contains("expires=3600"));
+
+    // Det maskerade värdet matchar vilket värde som helst vid uppspelning
+    let agent = recording::agent_spela_upp(katalog.path()).unwrap();
+    let text = agent
+        .get("http://127.0.0.1:9/login?username=annan&page=1")
+        .call()
+        .unwrap()
+        .body_mut()
+        .read_to_string()
+        .unwrap();
+    assert_eq!(text, format!("access_token={}&expires=3600\nToken: {}\nOK", recording::REDACTED, recording::REDACTED));
+}
@@ -0,0 +1,69 @@
+use super::ORGNR;
+use super::easycashier_server::{FakeEasyCashier, zrapport};
+use crate::backend::ZReportSource;
+use crate::easycashier::EasyCashier;
+use crate::recording;
+use crate::schema::SchemaBaseline;
+use crate::zrapport::DateRequest;
+use chrono::NaiveDate;
+use serde_json::{Value, json};
+
+fn intervall() -> DateRequest {
+    let datum = NaiveDate::from_ymd_opt(2025, 3, 10);
+    DateRequest::new(&datum, &datum)
+}
+
+#[test]
+fn avvikelser_i_svaret_rapporteras_innan_rapporterna_tolkas() {
+    let mut saknar_falt = zrapport(ORGNR, 1, 1, "2025-03-10T12:00:00", 100);
+    saknar_falt.as_object_mut().unwrap().remove("firstReceipt");
+    let mut nytt_falt = zrapport(ORGNR, 1, 2, "2025-03-10T14:00:00", 100);
+    nytt_falt["discountTotal"] = json!(0);
+    nytt_falt["zReportTransactions"][0]["cardType"] = json!("VISA");
+    let mut null_falt = zrapport(ORGNR, 1, 3, "2025-03-10T16:00:00", 100);
+    null_falt["storeName"] = Value::Null;
+    let server = FakeEasyCashier::start(ORGNR, vec![saknar_falt, nytt_falt, null_falt]);
+    let easy = EasyCashier::login(recording::agent(), &server.server.url, "test", "hemligt", "").unwrap();
+
+    // Den saknade nyckeln gör att listningen inte kan tolkas
+    assert!(easy.list_reports(&intervall()).is_err());
+
+    let sidor = easy.zrapporter_sidor(&intervall()).unwrap();
+    let baslinje = SchemaBaseline::default();
+    assert!(baslinje.kontrollera_meta(&sidor[0]).is_none());
+    let avvikelser = baslinje
+        .kontrollera_sida(&sidor[0])
+        .iter()
+        .map(|r| {
+            let text = r.avvikelser.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+            (r.rapport, text.join("; "))
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        avvikelser,
+        [
+            (Some(1), "zReport: saknade nycklar: firstReceipt".to_string()),
+            (
+                Some(2),
+                "zReport: oväntade nycklar: discountTotal; zReportTransactions[0]: oväntade nycklar: cardType"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn avvikande_sidinformation_rapporteras() {
+    let mut baslinje = SchemaBaseline::default();
+    let sida = json!({
+        "metaInformation": { "currentPage": 1, "pageCount": 1, "totalResources": 0 },
+        "items": [],
+    });
+    let avvikelse = baslinje.kontrollera_meta(&sida).unwrap();
+    assert_eq!(avvikelse.ovantade, ["pageCount"]);
+    assert_eq!(avvikelse.saknade, ["totalPages"]);
+
+    baslinje.uppdatera_sida(&sida);
+    assert_eq!(baslinje.kontrollera_meta(&sida).unwrap().saknade, ["totalPages"]);
+    assert!(baslinje.meta_information.contains("pageCount"));
+}
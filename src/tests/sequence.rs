@@ -0,0 +1,50 @@
+use super::rapport;
+use crate::zrapport::ZRapport;
+use crate::sequence::{self, Kassa};
+
+fn rapporter(store: u32, nummer: &[u32]) -> Vec<ZRapport> {
+    nummer
+        .iter()
+        .map(|&nr| rapport(store, nr, "2025-03-10T18:00:00", 100))
+        .collect()
+}
+
+fn luckor(rapporter: &[ZRapport], from: Option<u32>, to: Option<u32>) -> Vec<String> {
+    sequence::luckor(rapporter, from, to)
+        .iter()
+        .map(|l| l.to_string())
+        .collect()
+}
+
+#[test]
+fn saknade_nummer_i_slutet_av_intervallet_upptacks() {
+    let lst = rapporter(1, &[3, 4, 6]);
+    assert_eq!(luckor(&lst, None, None), ["Bu: 1 Ka: 1 Nr: 5"]);
+    assert_eq!(
+        luckor(&lst, Some(1), Some(9)),
+        [
+            "Bu: 1 Ka: 1 Nr: 1 - 2",
+            "Bu: 1 Ka: 1 Nr: 5",
+            "Bu: 1 Ka: 1 Nr: 7 - 9"
+        ]
+    );
+    assert_eq!(
+        luckor(&lst, None, Some(7)),
+        ["Bu: 1 Ka: 1 Nr: 5", "Bu: 1 Ka: 1 Nr: 7"]
+    );
+    assert_eq!(luckor(&lst, Some(3), Some(6)), ["Bu: 1 Ka: 1 Nr: 5"]);
+}
+
+#[test]
+fn kassa_tolkas_som_butik_och_kassa() {
+    let kassa = sequence::parse_kassa("2:1").unwrap();
+    assert_eq!(
+        kassa,
+        Kassa {
+            store: 2,
+            register: 1
+        }
+    );
+    assert_eq!(kassa.to_string(), "Bu: 2 Ka: 1");
+    assert!(sequence::parse_kassa("2").is_err());
+}
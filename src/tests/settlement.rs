@@ -0,0 +1,243 @@
+use super::{datum, rapport};
+use crate::ledger::{JournalEntry, JournalEntryAccount, JournalEntryNumber};
+use crate::settlement::{self, Inlosare, Koppling, Utbetalning};
+use crate::zrapport::ZRapport;
+use rust_decimal::Decimal;
+
+fn kr(s: &str) -> Decimal {
+    s.parse().unwrap()
+}
+
+#[test]
+fn tolkar_avrakningsfiler_fran_alla_inlosare() {
+    let nets = "\u{feff}Settlement date;Transaction date;Settlement reference;Gross amount;Fee amount;Net amount\n\
+                12.03.2025;10.03.2025;NETS-1;100.00;1.50;98.50\n\
+                12.03.2025;11.03.2025;NETS-1;200.00;3.00;197.00\n";
+    let worldline = "Payout date,Transaction date,Payout reference,Gross amount,Commission,Net amount\n\
+                     2025-03-12,2025-03-10T14:03:00,\"WL 1\",\"1,250.00\",-12.50,1237.50\n";
+    let bambora = "Utbetalningsdatum\tTransaktionsdatum\tUtbetalningsreferens\tBelopp\tAvgift\tUtbetalt belopp\n\
+                   2025-03-13\t2025-03-11\tB-7\t1 000,00\t9,90\t990,10\n\
+                   \n\
+                   2025-03-12\t2025-03-10\tB-6\t50,00\t0,50\t49,50\n";
+
+    let lst = settlement::tolka(nets).unwrap();
+    assert_eq!(lst.len(), 1);
+    assert_eq!(lst[0].inlosare, Inlosare::Nets);
+    assert_eq!(lst[0].datum, datum("2025-03-12"));
+    assert_eq!(
+        lst[0].transaktionsdatum.iter().copied().collect::<Vec<_>>(),
+        [datum("2025-03-10"), datum("2025-03-11")]
+    );
+    assert_eq!((lst[0].brutto, lst[0].avgift, lst[0].netto), (kr("300"), kr("4.5"), kr("295.5")));
+    assert_eq!(lst[0].titel(), "Kortinlösen, Nets NETS-1");
+
+    let lst = settlement::tolka(worldline).unwrap();
+    assert_eq!(lst[0].inlosare, Inlosare::Worldline);
+    assert_eq!(lst[0].referens, "WL 1");
+    assert_eq!((lst[0].brutto, lst[0].avgift, lst[0].netto), (kr("1250"), kr("12.5"), kr("1237.5")));
+
+    let lst = settlement::tolka(bambora).unwrap();
+    assert_eq!(lst.iter().map(|u| u.referens.as_str()).collect::<Vec<_>>(), ["B-6", "B-7"]);
+    assert_eq!(lst[1].inlosare, Inlosare::Bambora);
+    assert_eq!(lst[1].netto, kr("990.10"));
+}
+
+#[test]
+fn sista_skiljetecknet_ar_decimaltecken() {
+    let fil = |belopp: &str| {
+        format!(
+            "Settlement date;Transaction date;Settlement reference;Gross amount;Fee amount;Net amount\n\
+             12.03.2025;10.03.2025;N1;{};0;{}\n",
+            belopp, belopp
+        )
+    };
+    for (text, varde) in [
+        ("1.250,00", "1250"),
+        ("1,250.00", "1250"),
+        ("1.250.000", "1250000"),
+        ("1 250,5", "1250.5"),
+        ("-12,50", "-12.5"),
+        ("125", "125"),
+    ] {
+        let lst = settlement::tolka(&fil(text)).unwrap_or_else(|err| panic!("{}: {}", text, err));
+        assert_eq!(lst[0].brutto, kr(varde), "{}", text);
+    }
+    for text in ["1,250", "1.250", "1.25,00", "1,250.000,00", "1.2.3"] {
+        assert!(settlement::tolka(&fil(text)).is_err(), "{}", text);
+    }
+}
+
+#[test]
+fn felaktiga_avrakningsfiler_ger_fel() {
+    let err = settlement::tolka("Datum;Summa\n2025-03-12;100\n").unwrap_err();
+    assert!(err.contains("okänt filformat"), "{}", err);
+
+    let err = settlement::tolka(
+        "Payout date,Transaction date,Payout reference,Gross amount,Commission,Net amount\n\
+         2025-03-12,2025-03-10,WL,100.00,1.00,98.00\n",
+    )
+    .unwrap_err();
+    assert!(err.starts_with("rad 2: nettobeloppet"), "{}", err);
+
+    let err = settlement::tolka(
+        "Payout date,Transaction date,Payout reference,Gross amount,Commission,Net amount\n\
+         2025-03-12,10/03/2025,WL,100.00,1.00,99.00\n",
+    )
+    .unwrap_err();
+    assert!(err.contains("ogiltigt datum"), "{}", err);
+}
+
+#[test]
+fn utbetalning_kopplas_till_z_rapporter_och_nollar_1580() {
+    let rapporter = rapporter(&[
+        (1, "2025-03-09T18:00:00", 500),
+        (2, "2025-03-10T18:00:00", 100),
+        (3, "2025-03-11T18:00:00", 200),
+        (4, "2025-03-12T18:00:00", 700),
+    ]);
+    let lst = settlement::tolka(
+        "Settlement date;Transaction date;Settlement reference;Gross amount;Fee amount;Net amount\n\
+         12.03.2025;10.03.2025;N1;100.00;1.50;98.50\n\
+         12.03.2025;11.03.2025;N1;200.00;3.00;197.00\n",
+    )
+    .unwrap();
+
+    let kopplingar = settlement::koppla(&[&lst[0]], &rapporter, &[], 0);
+    assert_eq!(kopplingar.len(), 1);
+    let koppling = &kopplingar[0];
+    assert_eq!(koppling.rapporter.iter().map(|r| r.sequence_number).collect::<Vec<_>>(), [2, 3]);
+    assert_eq!(koppling.kort, kr("300"));
+    assert!(koppling.differens().is_zero());
+
+    let entry = settlement::create_journal_entry(&lst[0], koppling, 1930, 6570);
+    assert_eq!(entry.title, "Kortinlösen, Nets N1");
+    assert_eq!(entry.date, datum("2025-03-12"));
+    let rader = entry
+        .items
+        .iter()
+        .map(|i| (i.account, i.debit, i.credit))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rader,
+        [
+            (1930, kr("295.5"), kr("0")),
+            (6570, kr("4.5"), kr("0")),
+            (1580, kr("0"), kr("300")),
+        ]
+    );
+    assert_eq!(entry.items[2].description.as_deref(), Some("Z-Rapport 1:1:2, 1:1:3"));
+}
+
+fn rapporter(lst: &[(u32, &str, i64)]) -> Vec<ZRapport> {
+    lst.iter()
+        .map(|(nr, tid, kort)| rapport(1, *nr, tid, *kort))
+        .collect()
+}
+
+#[test]
+fn utbetalningar_for_samma_dag_jamfors_tillsammans() {
+    let rapporter = rapporter(&[(1, "2025-03-10T18:00:00", 300), (2, "2025-03-11T18:00:00", 200)]);
+    let lst = settlement::tolka(
+        "Settlement date;Transaction date;Settlement reference;Gross amount;Fee amount;Net amount\n\
+         12.03.2025;10.03.2025;VISA;100.00;1.00;99.00\n\
+         12.03.2025;10.03.2025;MC;200.00;2.00;198.00\n\
+         13.03.2025;11.03.2025;VISA-2;200.00;2.00;198.00\n",
+    )
+    .unwrap();
+    let utbetalningar = lst.iter().collect::<Vec<_>>();
+
+    let kopplingar = settlement::koppla(&utbetalningar, &rapporter, &[], 0);
+    assert_eq!(kopplingar.len(), 2);
+    assert_eq!(
+        kopplingar[0].utbetalningar.iter().map(|u| u.referens.as_str()).collect::<Vec<_>>(),
+        ["MC", "VISA"]
+    );
+    assert_eq!(kopplingar[0].rapporter.iter().map(|r| r.sequence_number).collect::<Vec<_>>(), [1]);
+    assert!(kopplingar[0].differens().is_zero());
+    assert_eq!(kopplingar[1].utbetalningar[0].referens, "VISA-2");
+    assert!(kopplingar[1].differens().is_zero());
+}
+
+/// Verifikatet som bokföringen skulle skapa för utbetalningen.
+fn bokfor(utbetalning: &Utbetalning, koppling: &Koppling, nummer: u32) -> JournalEntry {
+    let create = settlement::create_journal_entry(utbetalning, koppling, 1930, 6570);
+    JournalEntry {
+        id: nummer.to_string(),
+        title: create.title,
+        journal_entry_number: JournalEntryNumber {
+            series: "V".to_string(),
+            separator: String::new(),
+            number: nummer,
+        },
+        date: create.date,
+        items: create
+            .items
+            .into_iter()
+            .zip(1..)
+            .map(|(item, id)| JournalEntryAccount {
+                id,
+                account: item.account,
+                debit: item.debit,
+                credit: item.credit,
+                description: item.description,
+            })
+            .collect(),
+        reversing_journal_entry_id: None,
+        reversed_by_journal_entry_id: None,
+    }
+}
+
+#[test]
+fn z_rapport_raknas_inte_tva_ganger() {
+    let rapporter = rapporter(&[(1, "2025-03-10T18:00:00", 300), (2, "2025-03-11T18:00:00", 200)]);
+    let lst = settlement::tolka(
+        "Settlement date;Transaction date;Settlement reference;Gross amount;Fee amount;Net amount\n\
+         12.03.2025;10.03.2025;N1;300.00;3.00;297.00\n\
+         14.03.2025;10.03.2025;N2;300.00;3.00;297.00\n\
+         14.03.2025;11.03.2025;N2;200.00;2.00;198.00\n",
+    )
+    .unwrap();
+    let kopplingar = settlement::koppla(&[&lst[0]], &rapporter, &[], 0);
+    let bokford = bokfor(&lst[0], &kopplingar[0], 1);
+
+    let tackningar = settlement::tackningar(&[bokford]);
+    assert_eq!(tackningar.len(), 1);
+    assert_eq!(tackningar[0].rapporter.iter().map(|id| id.to_string()).collect::<Vec<_>>(), ["1:1:1"]);
+    assert_eq!(tackningar[0].brutto, kr("300"));
+    let kopplingar = settlement::koppla(&[&lst[1]], &rapporter, &tackningar, 0);
+    assert_eq!(kopplingar[0].rapporter.iter().map(|r| r.sequence_number).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!((kopplingar[0].kort, kopplingar[0].bokfort), (kr("500"), kr("300")));
+    assert_eq!(kopplingar[0].differens(), kr("300"));
+}
+
+#[test]
+fn resten_av_en_dag_bokfors_i_en_senare_korning() {
+    let rapporter = rapporter(&[(1, "2025-03-10T18:00:00", 300), (2, "2025-03-11T18:00:00", 400)]);
+    let lst = settlement::tolka(
+        "Settlement date;Transaction date;Settlement reference;Gross amount;Fee amount;Net amount\n\
+         12.03.2025;10.03.2025;VISA;100.00;1.00;99.00\n\
+         12.03.2025;10.03.2025;MC;200.00;2.00;198.00\n\
+         13.03.2025;10.03.2025;AMEX;50.00;0.50;49.50\n",
+    )
+    .unwrap();
+    let (mc, visa) = (&lst[0], &lst[1]);
+
+    // Första körningen: båda stämmer tillsammans, men bara VISA hann bokföras
+    let kopplingar = settlement::koppla(&[mc, visa], &rapporter, &[], 0);
+    assert!(kopplingar[0].differens().is_zero());
+    let bokford = bokfor(visa, &kopplingar[0], 1);
+
+    // Andra körningen: MC stämmer mot det som är kvar av dagen
+    let tackningar = settlement::tackningar(std::slice::from_ref(&bokford));
+    let kopplingar = settlement::koppla(&[mc], &rapporter, &tackningar, 0);
+    assert_eq!(kopplingar[0].rapporter.iter().map(|r| r.sequence_number).collect::<Vec<_>>(), [1]);
+    assert_eq!(kopplingar[0].kvar(), kr("200"));
+    assert!(kopplingar[0].differens().is_zero());
+    let bokford_mc = bokfor(mc, &kopplingar[0], 2);
+
+    // En tredje utbetalning för dagen har inget kvar att stämma mot
+    let tackningar = settlement::tackningar(&[bokford, bokford_mc]);
+    let kopplingar = settlement::koppla(&[&lst[2]], &rapporter, &tackningar, 0);
+    assert!(kopplingar[0].kvar().is_zero());
+    assert_eq!(kopplingar[0].differens(), kr("50"));
+}
@@ -0,0 +1,42 @@
+use super::rapport;
+use crate::title::TitleTemplate;
+
+#[test]
+fn mallen_maste_identifiera_kassan() {
+    for mall in ["Z {sequence}", "Z {store}-{sequence}", "Z {register}-{sequence}"] {
+        let err = TitleTemplate::new(mall).unwrap_err();
+        assert!(err.contains("måste innehålla"), "{}: {}", mall, err);
+    }
+    assert!(TitleTemplate::new("Z {store}:{register}:{sequence} {date}").is_ok());
+}
+
+#[test]
+fn samma_z_nummer_i_annan_kassa_matchar_inte() {
+    let mall = TitleTemplate::new("Z {store}:{register}:{sequence}").unwrap();
+    let rapport = rapport(1, 7, "2025-03-10T18:00:00", 100);
+
+    assert!(mall.matchar("Z 1:1:7", &rapport));
+    assert!(!mall.matchar("Z 2:1:7", &rapport));
+    assert!(!mall.matchar("Z 1:2:7", &rapport));
+    assert!(mall.avser_nummer("Z 1:1:7", 1, 1, 7));
+    assert!(!mall.avser_nummer("Z 1:2:7", 1, 1, 7));
+}
+
+#[test]
+fn foretagsnamn_med_mallens_text_matchar() {
+    let mall = TitleTemplate::new("Z {company} Nr: {sequence} Bu: {store} Ka: {register}").unwrap();
+    let mut rapport = rapport(1, 7, "2025-03-10T18:00:00", 100);
+    rapport.company_name = "Nr: 1 Bu: Kaffe Nr: AB".to_string();
+
+    let titel = mall.format(&rapport, None);
+    let fields = mall.parse(&titel).unwrap();
+    assert_eq!((fields.store, fields.register, fields.sequence), (Some(1), Some(1), Some(7)));
+    assert!(mall.matchar(&titel, &rapport));
+    assert!(!mall.matchar("Z Nr: 1 Bu: Kaffe Nr: AB Nr: 8 Bu: 1 Ka: 1", &rapport));
+
+    // Fälten efter företagsnamnet tolkas från slutet
+    rapport.company_name = "Kaffe Nr: 7 Bu: 1 Ka: 1".to_string();
+    let fields = mall.parse(&mall.format(&rapport, None)).unwrap();
+    assert_eq!((fields.store, fields.register, fields.sequence), (Some(1), Some(1), Some(7)));
+    assert!(!mall.matchar("Z Kaffe Nr: 7 Bu: 1 Ka: 1 Nr: 8 Bu: 1 Ka: 1", &rapport));
+}
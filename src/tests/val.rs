@@ -0,0 +1,32 @@
+use crate::tolka_val;
+
+const MOJLIGA: [u32; 6] = [120, 121, 122, 125, 128, 130];
+
+#[test]
+fn nummer_intervall_och_listor() {
+    assert_eq!(tolka_val("121", &MOJLIGA).unwrap(), [121]);
+    assert_eq!(tolka_val("128 120", &MOJLIGA).unwrap(), [120, 128]);
+    // Intervall tar bara med nummer som går att importera
+    assert_eq!(tolka_val("120-125", &MOJLIGA).unwrap(), [120, 121, 122, 125]);
+    assert_eq!(tolka_val("119-121 130", &MOJLIGA).unwrap(), [120, 121, 130]);
+    assert_eq!(tolka_val("alla", &MOJLIGA).unwrap(), MOJLIGA);
+    assert_eq!(tolka_val("*", &MOJLIGA).unwrap(), MOJLIGA);
+}
+
+#[test]
+fn undantag() {
+    assert_eq!(tolka_val("!128", &MOJLIGA).unwrap(), [120, 121, 122, 125, 130]);
+    assert_eq!(tolka_val("!120-122 !130", &MOJLIGA).unwrap(), [125, 128]);
+    assert_eq!(tolka_val("120-128 !121-125", &MOJLIGA).unwrap(), [120, 128]);
+    assert_eq!(tolka_val("alla !125", &MOJLIGA).unwrap(), [120, 121, 122, 128, 130]);
+}
+
+#[test]
+fn ogiltiga_val_ger_fel() {
+    // Nummer och intervall utan importerbara rapporter
+    assert_eq!(tolka_val("123", &MOJLIGA).unwrap_err(), "123");
+    assert_eq!(tolka_val("121 131-140", &MOJLIGA).unwrap_err(), "131-140");
+    for input in ["abc", "12x", "120-", "-125", "!abc", "1-2-3"] {
+        assert_eq!(tolka_val(input, &MOJLIGA).unwrap_err(), input, "{}", input);
+    }
+}
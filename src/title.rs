@@ -0,0 +1,203 @@
+use crate::zrapport::ZRapport;
+use crate::utils::format_local_date;
+use chrono::NaiveDate;
+
+/// Verifikatnamn som användes innan mallen blev konfigurerbar.
+pub const LEGACY_TITLE_TEMPLATE: &str =
+    "Z, Bu: {store} Ka: {register} Nr: {sequence} Kv: {first} - {last}";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    Store,
+    Register,
+    Sequence,
+    First,
+    Last,
+    Date,
+    Company,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "store" => Some(Placeholder::Store),
+            "register" => Some(Placeholder::Register),
+            "sequence" => Some(Placeholder::Sequence),
+            "first" => Some(Placeholder::First),
+            "last" => Some(Placeholder::Last),
+            "date" => Some(Placeholder::Date),
+            "company" => Some(Placeholder::Company),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(Placeholder),
+}
+
+/// Mall för verifikatnamn med platshållarna `{store}`, `{register}`,
+/// `{sequence}`, `{first}`, `{last}`, `{date}` och `{company}`.
+#[derive(Debug, Clone)]
+pub struct TitleTemplate {
+    parts: Vec<Part>,
+}
+
+/// Värden som tolkats ur ett verifikatnamn.
+#[derive(Debug, Default, Clone)]
+pub struct TitleFields {
+    pub store: Option<u32>,
+    pub register: Option<u32>,
+    pub sequence: Option<u32>,
+    pub first: Option<u32>,
+    pub last: Option<u32>,
+}
+
+/// Sätt fältet till värdet, `false` om värdet inte kan tolkas.
+fn satt(field: Placeholder, value: &str, fields: &mut TitleFields) -> bool {
+    let number = || value.parse::<u32>().ok();
+    let varde = match field {
+        Placeholder::Store => &mut fields.store,
+        Placeholder::Register => &mut fields.register,
+        Placeholder::Sequence => &mut fields.sequence,
+        Placeholder::First => &mut fields.first,
+        Placeholder::Last => &mut fields.last,
+        Placeholder::Date => return value.parse::<NaiveDate>().is_ok(),
+        Placeholder::Company => return true,
+    };
+    *varde = number();
+    varde.is_some()
+}
+
+/// Tolka resten av verifikatnamnet med mallens återstående delar. Ett värde
+/// sträcker sig till nästa text i mallen, men eftersom t.ex. företagsnamnet
+/// kan innehålla samma text prövas varje förekomst av texten. För
+/// företagsnamnet prövas den sista först, så att fälten efter det tolkas från
+/// slutet av namnet.
+fn tolka(parts: &[Part], rest: &str, fields: &mut TitleFields) -> bool {
+    match parts {
+        [] => rest.is_empty(),
+        [Part::Text(text), tail @ ..] => rest
+            .strip_prefix(text.as_str())
+            .is_some_and(|rest| tolka(tail, rest, fields)),
+        [Part::Field(field), tail @ ..] => {
+            let mut slut = match tail.first() {
+                Some(Part::Text(next)) => rest.match_indices(next.as_str()).map(|(i, _)| i).collect(),
+                _ => vec![rest.len()],
+            };
+            if *field == Placeholder::Company {
+                slut.reverse();
+            }
+            slut.into_iter().any(|end| {
+                let mut forsok = fields.clone();
+                let ok = satt(*field, &rest[..end], &mut forsok) && tolka(tail, &rest[end..], &mut forsok);
+                if ok {
+                    *fields = forsok;
+                }
+                ok
+            })
+        }
+    }
+}
+
+impl TitleTemplate {
+    pub fn new(template: &str) -> Result<Self, String> {
+        let mut parts: Vec<Part> = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}').map(|i| start + i) else {
+                return Err(format!("{}: saknar avslutande }}", template));
+            };
+            let name = &rest[start + 1..end];
+            let field = Placeholder::from_name(name)
+                .ok_or_else(|| format!("{}: okänd platshållare {{{}}}", template, name))?;
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            if let Some(Part::Field(_)) = parts.last() {
+                return Err(format!(
+                    "{}: platshållare måste separeras med text",
+                    template
+                ));
+            }
+            parts.push(Part::Field(field));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        // Z-nummer är bara unika per kassa
+        for (field, name) in [
+            (Placeholder::Store, "store"),
+            (Placeholder::Register, "register"),
+            (Placeholder::Sequence, "sequence"),
+        ] {
+            if !parts.contains(&Part::Field(field)) {
+                return Err(format!("{}: mallen måste innehålla {{{}}}", template, name));
+            }
+        }
+
+        Ok(TitleTemplate { parts })
+    }
+
+    pub fn legacy() -> Self {
+        Self::new(LEGACY_TITLE_TEMPLATE).unwrap()
+    }
+
+    /// Text före första platshållaren, alla verifikatnamn med mallen börjar med denna.
+    pub fn prefix(&self) -> &str {
+        match self.parts.first() {
+            Some(Part::Text(text)) => text,
+            _ => "",
+        }
+    }
+
+    pub fn format(&self, rapport: &ZRapport, datum: Option<NaiveDate>) -> String {
+        let mut title = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(text) => title.push_str(text),
+                Part::Field(field) => title.push_str(&match field {
+                    Placeholder::Store => rapport.store_number.to_string(),
+                    Placeholder::Register => rapport.cash_register_number.to_string(),
+                    Placeholder::Sequence => rapport.sequence_number.to_string(),
+                    Placeholder::First => rapport.first_receipt.to_string(),
+                    Placeholder::Last => rapport.last_receipt.to_string(),
+                    Placeholder::Date => datum.as_ref().map_or("?".to_string(), format_local_date),
+                    Placeholder::Company => rapport.company_name.clone(),
+                }),
+            }
+        }
+        title
+    }
+
+    /// Tolka ett verifikatnamn med mallen, `None` om namnet inte följer mallen.
+    pub fn parse(&self, title: &str) -> Option<TitleFields> {
+        let mut fields = TitleFields::default();
+        tolka(&self.parts, title, &mut fields).then_some(fields)
+    }
+
+    /// Kontrollera om verifikatnamnet följer mallen och avser Z-nummer `nr`
+    /// för kassan `store`/`register`.
+    pub fn avser_nummer(&self, title: &str, store: u32, register: u32, nr: u32) -> bool {
+        self.parse(title).is_some_and(|fields| {
+            fields.sequence == Some(nr) && fields.store == Some(store) && fields.register == Some(register)
+        })
+    }
+
+    /// Kontrollera om verifikatnamnet följer mallen och avser rapporten.
+    pub fn matchar(&self, title: &str, rapport: &ZRapport) -> bool {
+        let Some(fields) = self.parse(title) else {
+            return false;
+        };
+        let eq = |value: Option<u32>, expected: u32| value.is_none_or(|v| v == expected);
+        fields.sequence == Some(rapport.sequence_number)
+            && fields.store == Some(rapport.store_number)
+            && fields.register == Some(rapport.cash_register_number)
+            && eq(fields.first, rapport.first_receipt)
+            && eq(fields.last, rapport.last_receipt)
+    }
+}
@@ -0,0 +1,295 @@
+use crate::RapportImport;
+use crate::ledger::{CreateJournal, JournalEntry};
+use crate::mapping::Mapping;
+use crate::utils::format_local_date;
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{
+    Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode,
+    enable_raw_mode,
+};
+use crossterm::{cursor, event, queue};
+use std::io::{IsTerminal, Write};
+
+/// Använd helskärmsläget bara när både in- och utdata är en terminal.
+pub fn tillganglig() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Återställer terminalen även om programmet avbryts av panic.
+struct Terminal;
+
+impl Terminal {
+    fn start() -> std::io::Result<Self> {
+        enable_raw_mode()?;
+        queue!(std::io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        std::io::stdout().flush()?;
+        Ok(Terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        queue!(std::io::stdout(), cursor::Show, LeaveAlternateScreen).ok();
+        std::io::stdout().flush().ok();
+        disable_raw_mode().ok();
+    }
+}
+
+struct Vy<'a> {
+    rapporter: &'a mut [RapportImport],
+    /// Kolumner i listan.
+    mapping: &'a Mapping,
+    /// Index för markerad rad.
+    markor: usize,
+    /// Första raden som visas i listan.
+    forsta: usize,
+    /// Valda rapporter för import.
+    valda: Vec<bool>,
+    /// Status per rapport under och efter import.
+    status: Vec<String>,
+    /// Senaste raderna från importen.
+    logg: Vec<String>,
+}
+
+fn klipp(s: &str, bredd: usize) -> String {
+    s.chars().take(bredd).collect()
+}
+
+fn kolumnbredd(rubrik: &str) -> usize {
+    rubrik.chars().count().max(9)
+}
+
+impl Vy<'_> {
+    fn rad(&self, i: usize) -> String {
+        let e = &self.rapporter[i];
+        let rapport = &e.rapport;
+        let vald = if self.valda[i] { "[x]" } else { "[ ]" };
+        let marker = if e.verifikat.is_some() {
+            "✓"
+        } else if e.stangd.is_some() {
+            "✗"
+        } else {
+            " "
+        };
+        let datum = e.datum.as_ref().map_or("?".to_string(), format_local_date);
+        let vernr = e
+            .verifikat
+            .as_ref()
+            .map_or("".to_string(), |j| j.journal_entry_number.to_string());
+        let mut rad = format!("{} {} {:4} {:<10}", vald, marker, rapport.sequence_number, datum);
+        for (belopp, r) in self.mapping.belopp(rapport).iter().zip(self.mapping.rubriker()) {
+            let b = kolumnbredd(r);
+            rad += &format!(" {:>b$.2}", belopp);
+        }
+        format!("{} {:<6} {}", rad, vernr, self.status[i])
+    }
+
+    fn detaljer(&self, forslag: Option<CreateJournal>) -> Vec<String> {
+        let e = &self.rapporter[self.markor];
+        let rapport = &e.rapport;
+        let mut rader = vec![
+            format!("{}  ({})", e.titel, rapport.date_created),
+            String::new(),
+            "TRANSAKTIONER".to_string(),
+        ];
+        for tr in rapport.z_report_transactions.iter() {
+            rader.push(format!(
+                "  {:>5} {:>12.2}  {}",
+                tr.account_number,
+                tr.amount,
+                tr.description.as_deref().unwrap_or("")
+            ));
+        }
+
+        if let Some(stangd) = &e.stangd {
+            rader.push(String::new());
+            rader.push(format!("Kan inte bokföras: {}", stangd));
+        }
+
+        if let Some(journal) = forslag.filter(|_| e.verifikat.is_none()) {
+            rader.push(String::new());
+            rader.push(format!(
+                "FÖRESLAGET VERIFIKAT {}",
+                format_local_date(&journal.date)
+            ));
+            rader.push(format!("  {:>5} {:>12} {:>12}", "KONTO", "DEBET", "KREDIT"));
+            for item in journal.items.iter() {
+                rader.push(format!(
+                    "  {:>5} {:>12.2} {:>12.2}  {}",
+                    item.account,
+                    item.debit,
+                    item.credit,
+                    item.description.as_deref().unwrap_or("")
+                ));
+            }
+        }
+        rader
+    }
+
+    fn rita(&mut self, forslag: &dyn Fn(&RapportImport) -> Option<CreateJournal>) -> std::io::Result<()> {
+        let (bredd, hojd) = crossterm::terminal::size()?;
+        let (bredd, hojd) = (bredd as usize, hojd as usize);
+        let listhojd = (hojd.saturating_sub(4) / 2).max(3);
+        if self.markor < self.forsta {
+            self.forsta = self.markor;
+        } else if self.markor >= self.forsta + listhojd {
+            self.forsta = self.markor + 1 - listhojd;
+        }
+
+        let mut out = std::io::stdout();
+        queue!(out, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+        let mut rubrik = "        NR DATUM     ".to_string();
+        for r in self.mapping.rubriker() {
+            let b = kolumnbredd(r);
+            rubrik += &format!(" {:>b$}", r);
+        }
+        rubrik += &format!(" VERNR  ({} valda)", self.valda.iter().filter(|v| **v).count());
+        queue!(
+            out,
+            SetAttribute(Attribute::Bold),
+            Print(klipp(&rubrik, bredd)),
+            SetAttribute(Attribute::Reset)
+        )?;
+
+        let slut = (self.forsta + listhojd).min(self.rapporter.len());
+        for (y, i) in (self.forsta..slut).enumerate() {
+            queue!(out, cursor::MoveTo(0, (y + 1) as u16))?;
+            if i == self.markor {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(out, Print(klipp(&self.rad(i), bredd)))?;
+            queue!(out, SetAttribute(Attribute::Reset))?;
+        }
+
+        let detaljstart = listhojd + 2;
+        queue!(out, cursor::MoveTo(0, (detaljstart - 1) as u16), Print("─".repeat(bredd)))?;
+        let detaljer = self.detaljer(forslag(&self.rapporter[self.markor]));
+        let logghojd = self.logg.len().min(3);
+        let loggstart = hojd.saturating_sub(1 + logghojd);
+        let detaljhojd = loggstart.saturating_sub(detaljstart);
+        for (y, rad) in (detaljstart..).zip(detaljer.iter().take(detaljhojd)) {
+            queue!(out, cursor::MoveTo(0, y as u16), Print(klipp(rad, bredd)))?;
+        }
+
+        for (y, rad) in (loggstart..).zip(self.logg.iter().skip(self.logg.len() - logghojd)) {
+            queue!(out, cursor::MoveTo(0, y as u16), Print(klipp(rad, bredd)))?;
+        }
+
+        let hjalp = "↑/↓ flytta  mellanslag välj  a alla/ingen  Enter importera  q avsluta";
+        queue!(
+            out,
+            cursor::MoveTo(0, hojd.saturating_sub(1) as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(klipp(&format!("{:<bredd$}", hjalp), bredd)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()
+    }
+
+    fn vaxla(&mut self, i: usize) {
+        if self.rapporter[i].importerbar() {
+            self.valda[i] = !self.valda[i];
+        }
+    }
+
+    fn vaxla_alla(&mut self) {
+        let alla = self
+            .rapporter
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.importerbar())
+            .all(|(i, _)| self.valda[i]);
+        for i in 0..self.rapporter.len() {
+            self.valda[i] = !alla && self.rapporter[i].importerbar();
+        }
+    }
+}
+
+/// Helskärmsläge för att välja och importera rapporter. Piltangenter
+/// flyttar markören, mellanslag väljer och Enter importerar valda rapporter
+/// (eller markerad rapport om ingen är vald).
+pub fn valj_och_importera(
+    rapporter: &mut [RapportImport],
+    mapping: &Mapping,
+    forslag: &dyn Fn(&RapportImport) -> Option<CreateJournal>,
+    importera: &mut dyn FnMut(&RapportImport, &mut dyn Write) -> Result<JournalEntry, String>,
+) -> std::io::Result<()> {
+    if rapporter.is_empty() {
+        return Ok(());
+    }
+
+    let _terminal = Terminal::start()?;
+    let antal = rapporter.len();
+    let mut vy = Vy {
+        rapporter,
+        mapping,
+        markor: 0,
+        forsta: 0,
+        valda: vec![false; antal],
+        status: vec![String::new(); antal],
+        logg: Vec::new(),
+    };
+
+    loop {
+        vy.rita(forslag)?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Up | KeyCode::Char('k') => vy.markor = vy.markor.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => vy.markor = (vy.markor + 1).min(antal - 1),
+            KeyCode::PageUp => vy.markor = vy.markor.saturating_sub(10),
+            KeyCode::PageDown => vy.markor = (vy.markor + 10).min(antal - 1),
+            KeyCode::Home => vy.markor = 0,
+            KeyCode::End => vy.markor = antal - 1,
+            KeyCode::Char(' ') => {
+                vy.vaxla(vy.markor);
+                vy.markor = (vy.markor + 1).min(antal - 1);
+            }
+            KeyCode::Char('a') => vy.vaxla_alla(),
+            KeyCode::Enter => {
+                let mut valda = (0..antal).filter(|i| vy.valda[*i]).collect::<Vec<_>>();
+                if valda.is_empty() && vy.rapporter[vy.markor].importerbar() {
+                    valda.push(vy.markor);
+                }
+
+                for i in valda {
+                    vy.markor = i;
+                    vy.status[i] = "Importerar...".to_string();
+                    vy.rita(forslag)?;
+
+                    let mut logg: Vec<u8> = Vec::new();
+                    let resultat = importera(&vy.rapporter[i], &mut logg);
+                    vy.logg.extend(
+                        String::from_utf8_lossy(&logg)
+                            .lines()
+                            .filter(|l| !l.trim().is_empty())
+                            .map(|l| l.to_string()),
+                    );
+                    vy.valda[i] = false;
+                    match resultat {
+                        Ok(journal_entry) => {
+                            vy.status[i] = "Importerad".to_string();
+                            vy.rapporter[i].verifikat.replace(journal_entry);
+                        }
+                        Err(msg) => {
+                            vy.status[i] = "Misslyckades".to_string();
+                            vy.logg.extend(msg.lines().map(|l| l.to_string()));
+                            break;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
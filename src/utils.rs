@@ -1,6 +1,7 @@
 use std::io::{IsTerminal, Write};
 use std::str::FromStr;
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
+use chrono_tz::Europe::Stockholm;
 
 #[derive(Debug)]
 pub struct PageReq {
@@ -14,7 +15,7 @@ pub fn format_local_date(date: &NaiveDate) -> String {
 
 pub const DEFAULT_USER_AGENT: &str =
     "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:140.0) Gecko/20100101 Firefox/140.0";
-pub const APPLICATION_JSON: &'static str = "application/json";
+pub const APPLICATION_JSON: &str = "application/json";
 
 fn read_prompt(prompt: &str) -> std::io::Result<String> {
     print!("{}", prompt);
@@ -34,7 +35,7 @@ pub fn read_prompt_trim(prompt: &str) -> String {
 fn read_password(prompt: &str) -> std::io::Result<String> {
     // IntelliJ console is broken giving "device not ready" for /dev/tty.
     // Strangely the builtin terminal works fine.
-    if std::io::stdin().is_terminal() && !std::env::var("BROKEN_TERMINAL").is_ok() {
+    if std::io::stdin().is_terminal() && std::env::var("BROKEN_TERMINAL").is_err() {
         rpassword::prompt_password(prompt)
     } else {
         read_prompt(prompt)
@@ -57,11 +58,7 @@ pub fn get_env(key: &str) -> String {
     get_env_or_default(key, "")
 }
 
-pub fn format_orgnr(orgnr: &str) -> String {
-    let mut orgnr = orgnr.trim().to_string();
-    let len = orgnr.len();
-    if orgnr.chars().all(|c| c.is_ascii_digit()) && [10, 12].contains(&len) {
-        orgnr = format!("{}-{}", &orgnr[0..len - 4], &orgnr[len - 4..len]);
-    }
-    orgnr
+/// Konvertera UTC till lokal tid i Europe/Stockholm.
+pub fn utc_to_stockholm(utc: NaiveDateTime) -> NaiveDateTime {
+    utc.and_utc().with_timezone(&Stockholm).naive_local()
 }
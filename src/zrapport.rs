@@ -0,0 +1,291 @@
+//! Z-Rapporter och datumintervall som delas av kassasystemen.
+//!
+//! Typerna följer EasyCashiers JSON-format, som också sparas bredvid
+//! underlagen och kontrolleras mot schemabaslinjen.
+
+use crate::orgnr::{Orgnr, OrgnrError};
+use crate::utils;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Timelike};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Typ av transaktion i en Z-Rapport.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum TransactionType {
+    Sale,
+    Return,
+    Payment,
+    Vat,
+    Rounding,
+    Other(String),
+}
+
+impl From<String> for TransactionType {
+    fn from(s: String) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "SALE" => TransactionType::Sale,
+            "RETURN" => TransactionType::Return,
+            "PAYMENT" => TransactionType::Payment,
+            "VAT" => TransactionType::Vat,
+            "ROUNDING" => TransactionType::Rounding,
+            _ => TransactionType::Other(s),
+        }
+    }
+}
+
+impl From<TransactionType> for String {
+    fn from(t: TransactionType) -> Self {
+        match t {
+            TransactionType::Sale => "SALE".to_string(),
+            TransactionType::Return => "RETURN".to_string(),
+            TransactionType::Payment => "PAYMENT".to_string(),
+            TransactionType::Vat => "VAT".to_string(),
+            TransactionType::Rounding => "ROUNDING".to_string(),
+            TransactionType::Other(s) => s,
+        }
+    }
+}
+
+/// Betalsätt för betalningstransaktioner.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum PaymentMethod {
+    Cash,
+    Card,
+    Swish,
+    GiftCard,
+    Invoice,
+    Other(String),
+}
+
+impl From<String> for PaymentMethod {
+    fn from(s: String) -> Self {
+        match s.to_ascii_uppercase().as_str() {
+            "CASH" => PaymentMethod::Cash,
+            "CARD" => PaymentMethod::Card,
+            "SWISH" => PaymentMethod::Swish,
+            "GIFT_CARD" | "GIFTCARD" => PaymentMethod::GiftCard,
+            "INVOICE" => PaymentMethod::Invoice,
+            _ => PaymentMethod::Other(s),
+        }
+    }
+}
+
+impl From<PaymentMethod> for String {
+    fn from(p: PaymentMethod) -> Self {
+        match p {
+            PaymentMethod::Cash => "CASH".to_string(),
+            PaymentMethod::Card => "CARD".to_string(),
+            PaymentMethod::Swish => "SWISH".to_string(),
+            PaymentMethod::GiftCard => "GIFT_CARD".to_string(),
+            PaymentMethod::Invoice => "INVOICE".to_string(),
+            PaymentMethod::Other(s) => s,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ZRapportTrans {
+    #[serde(rename = "accountNumber")]
+    pub account_number: u16,
+    #[serde(with = "crate::amount")]
+    pub amount: Decimal,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "vatCode", default, skip_serializing_if = "Option::is_none")]
+    pub vat_code: Option<String>,
+    #[serde(
+        rename = "vatRate",
+        with = "crate::amount::rate",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub vat_rate: Option<Decimal>,
+    #[serde(rename = "transactionType", default, skip_serializing_if = "Option::is_none")]
+    pub transaction_type: Option<TransactionType>,
+    #[serde(rename = "paymentMethod", default, skip_serializing_if = "Option::is_none")]
+    pub payment_method: Option<PaymentMethod>,
+    #[serde(flatten)]
+    pub other: HashMap<String, Value>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ZRapport {
+    #[serde(rename = "sequenceNumber")]
+    pub sequence_number: u32,
+    #[serde(rename = "storeNumber")]
+    pub store_number: u32,
+    #[serde(rename = "cashRegisterNumber")]
+    pub cash_register_number: u32,
+    #[serde(rename = "firstReceipt")]
+    pub first_receipt: u32,
+    #[serde(rename = "lastReceipt")]
+    pub last_receipt: u32,
+    #[serde(rename = "dateCreated")]
+    pub date_created: String,
+    #[serde(rename = "companyName")]
+    pub company_name: String,
+    #[serde(rename = "corporateIdentity")]
+    pub corporate_identity: String,
+    #[serde(rename = "storeName", default, skip_serializing_if = "Option::is_none")]
+    pub store_name: Option<String>,
+    #[serde(rename = "cashRegisterName", default, skip_serializing_if = "Option::is_none")]
+    pub cash_register_name: Option<String>,
+
+    #[serde(rename = "zReportTransactions")]
+    pub z_report_transactions: Vec<ZRapportTrans>,
+
+    #[serde(flatten)]
+    pub other: HashMap<String, Value>,
+}
+impl ZRapport {
+    /// Tidpunkt då rapporten skapades, i lokal tid för Europe/Stockholm.
+    ///
+    /// Tidsstämplar med tidszon (t.ex. `Z` eller `+01:00`) konverteras, tidsstämplar
+    /// utan tidszon antas redan vara i lokal tid.
+    pub fn tidpunkt(&self) -> Result<NaiveDateTime, String> {
+        let s = self.date_created.trim();
+        if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+            return Ok(utils::utc_to_stockholm(dt.naive_utc()));
+        }
+
+        ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+            .iter()
+            .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+            .ok_or_else(|| format!("ogiltig tidpunkt {:?}", self.date_created))
+    }
+
+    /// Bokföringsdatum för rapporten. Rapporter som stängs före klockan
+    /// `brytpunkt` (lokal tid) bokförs på föregående dag.
+    pub fn datum(&self, brytpunkt: u32) -> Result<NaiveDate, String> {
+        if let Ok(date) = self.date_created.trim().parse::<NaiveDate>() {
+            return Ok(date);
+        }
+
+        let tidpunkt = self.tidpunkt()?;
+        let date = tidpunkt.date();
+        if tidpunkt.hour() < brytpunkt {
+            date.pred_opt()
+                .ok_or_else(|| format!("ogiltig tidpunkt {:?}", self.date_created))
+        } else {
+            Ok(date)
+        }
+    }
+
+    pub fn id(&self) -> ZRapportId {
+        ZRapportId {
+            store_number: self.store_number,
+            cash_register_number: self.cash_register_number,
+            sequence_number: self.sequence_number,
+        }
+    }
+
+    pub fn orgnr(&self) -> Result<Orgnr, OrgnrError> {
+        self.corporate_identity.parse()
+    }
+
+    pub fn konto(&self, account: u16) -> Decimal {
+        self.z_report_transactions.iter()
+            .filter(|tr| tr.account_number == account)
+            .map(|tr| tr.amount)
+            .sum()
+    }
+
+    /// Försäljning exklusive moms, summan av intäktskonton (kontoklass 3)
+    /// med omvänt tecken.
+    pub fn forsaljning(&self) -> Decimal {
+        -self.z_report_transactions.iter()
+            .filter(|tr| (3000..4000).contains(&tr.account_number))
+            .map(|tr| tr.amount)
+            .sum::<Decimal>()
+    }
+}
+
+/// Identifierar en Z-Rapport, anges som `BUTIK:KASSA:NUMMER`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZRapportId {
+    pub store_number: u32,
+    pub cash_register_number: u32,
+    pub sequence_number: u32,
+}
+
+impl std::str::FromStr for ZRapportId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s
+            .split(':')
+            .map(|p| p.trim().parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| format!("{}: förväntade BUTIK:KASSA:NUMMER", s))?;
+        match parts[..] {
+            [store_number, cash_register_number, sequence_number] => Ok(ZRapportId {
+                store_number,
+                cash_register_number,
+                sequence_number,
+            }),
+            _ => Err(format!("{}: förväntade BUTIK:KASSA:NUMMER", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ZRapportId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.store_number, self.cash_register_number, self.sequence_number
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct DateRequest {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub date_type: String,
+}
+
+impl DateRequest {
+    pub fn new(start_date: &Option<NaiveDate>, end_date: &Option<NaiveDate>) -> Self {
+        let today = chrono::Local::now().date_naive();
+        let (start_date, end_date) = {
+            if let Some(start_date) = start_date {
+                if let Some(end_date) = end_date {
+                    (*start_date, *end_date)
+                } else {
+                    (*start_date, today)
+                }
+            } else if let Some(end_date) = end_date {
+                if today < *end_date {
+                    (today, *end_date)
+                } else {
+                    (*end_date, *end_date)
+                }
+            } else {
+                (today, today)
+            }
+        };
+
+        let date_type = {
+            if start_date == end_date {
+                if start_date == today {
+                    "today"
+                } else {
+                    "date"
+                }
+            } else {
+                "interval"
+            }
+        };
+
+        Self {
+            start_date,
+            end_date,
+            date_type: date_type.to_string(),
+        }
+    }
+}